@@ -0,0 +1,148 @@
+//! Benchmarks of the hashtable: read throughput by number of reader threads (compared with a
+//! RwLock'ed std HashMap), write throughput, and insert/remove churn with and without a node pool.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use urcu_ht::{RcuHt, RcuHtBuilder};
+
+/// Number of objects in the hashtable.
+const OBJECTS: u32 = 1024;
+
+/// Numbers of reader threads: powers of two, up to the number of cores.
+fn thread_counts() -> Vec<usize> {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::iter::successors(Some(1), |n| Some(n * 2))
+        .take_while(|n| *n <= cores)
+        .collect()
+}
+
+/// Run `read` `iters` times split between `threads` threads, and return the time taken.
+fn run_readers<F>(threads: usize, iters: u64, read: F) -> Duration
+where
+    F: Fn(u64) + Sync,
+{
+    let per_thread = iters.div_ceil(threads as u64);
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| read(per_thread));
+        }
+    });
+    start.elapsed()
+}
+
+fn reads(c: &mut Criterion) {
+    let ht = RcuHt::<u32, u32>::new(1024, 1024, 0, true).unwrap();
+    {
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..OBJECTS {
+            wrlock.insert_or_replace(i, i);
+        }
+    }
+    let map = RwLock::new((0..OBJECTS).map(|i| (i, i)).collect::<HashMap<u32, u32>>());
+
+    let mut group = c.benchmark_group("read");
+    for threads in thread_counts() {
+        group.throughput(Throughput::Elements(threads as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("urcu", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_readers(threads, iters * threads as u64, |n| {
+                        let thread = ht.thread();
+                        for i in 0..n {
+                            let rdlock = thread.rdlock();
+                            black_box(rdlock.get(&(i as u32 % OBJECTS)));
+                        }
+                    })
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("rwlock", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    run_readers(threads, iters * threads as u64, |n| {
+                        for i in 0..n {
+                            let map = map.read().unwrap();
+                            black_box(map.get(&(i as u32 % OBJECTS)));
+                        }
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn writes(c: &mut Criterion) {
+    let ht = RcuHt::<u32, u32>::new(1024, 1024, 0, true).unwrap();
+    let thread = ht.thread();
+
+    let mut group = c.benchmark_group("write");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("insert_remove", |b| {
+        let mut wrlock = thread.wrlock().unwrap();
+        let mut i = 0u32;
+        b.iter(|| {
+            i = (i + 1) % OBJECTS;
+            wrlock.insert_or_replace(i, i);
+            wrlock.remove(&i).unwrap();
+        })
+    });
+
+    group.bench_function("replace", |b| {
+        let mut wrlock = thread.wrlock().unwrap();
+        let mut i = 0u32;
+        b.iter(|| {
+            i = i.wrapping_add(1);
+            wrlock.insert_or_replace(i % OBJECTS, i);
+        })
+    });
+    group.finish();
+}
+
+fn churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("churn");
+    group.throughput(Throughput::Elements(OBJECTS as u64));
+
+    for node_pool in [0, OBJECTS as usize] {
+        let ht = RcuHtBuilder::new()
+            .node_pool(node_pool)
+            .build::<u32, u32>()
+            .unwrap();
+        let thread = ht.thread();
+        let name = if node_pool == 0 {
+            "system"
+        } else {
+            "node_pool"
+        };
+
+        // Insert then remove all objects, so that freed nodes can be reused by the next batch.
+        group.bench_function(name, |b| {
+            let mut wrlock = thread.wrlock().unwrap();
+            b.iter(|| {
+                for i in 0..OBJECTS {
+                    wrlock.insert_or_replace(i, i);
+                }
+                for i in 0..OBJECTS {
+                    wrlock.remove(&i).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reads, writes, churn);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+//! Replay random operation sequences against both RcuHt and std HashMap, and compare results.
+//!
+//! Run with `cargo fuzz run ops` from the repository root.
+#![no_main]
+
+use std::collections::HashMap;
+
+use libfuzzer_sys::fuzz_target;
+use urcu_ht::{Op, RcuHt};
+
+fuzz_target!(|data: &[u8]| {
+    let ht = RcuHt::<u8, u8>::new(4, 4, 0, true).unwrap();
+    let thread = ht.thread();
+    let mut wrlock = thread.wrlock().unwrap();
+    let mut reference = HashMap::new();
+
+    // each operation is encoded as 3 bytes: operation type, key and value
+    for chunk in data.chunks_exact(3) {
+        let (key, value) = (chunk[1], chunk[2]);
+        let (op, expected) = match chunk[0] % 5 {
+            0 => (Op::Insert(key, value), reference.insert(key, value)),
+            1 => (Op::Remove(key), reference.remove(&key)),
+            2 => (Op::Get(key), reference.get(&key).cloned()),
+            3 => {
+                reference.clear();
+                (Op::Clear, None)
+            }
+            _ => (Op::FlushReclamation, None),
+        };
+
+        assert_eq!(wrlock.apply_op(op), expected);
+    }
+
+    drop(wrlock);
+    let rdlock = thread.rdlock();
+    let mut expected: Vec<(u8, u8)> = reference.into_iter().collect();
+    expected.sort_unstable();
+    let found: Vec<(u8, u8)> = rdlock.iter_sorted().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(found, expected);
+});
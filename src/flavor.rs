@@ -0,0 +1,147 @@
+//! Calls to urcu lib depending on the RCU flavor.
+//!
+//! All flavor specific functions (initialization, thread registration, read-side critical
+//! sections, grace periods and reclamation) must be called from here, never directly from
+//! urcu_sys: they all use urcu_sys generic names, which are bound to the flavor urcu_sys was
+//! built for, so that functions of different flavors cannot be mixed.
+//! The hashtable itself is bound to this same flavor by urcu_sys::cds_lfht_new.
+
+/// Initialize urcu lib. It must be called once, before any other call.
+#[cfg(feature = "memb")]
+pub(crate) fn init() {
+    unsafe {
+        urcu_sys::rcu_init();
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of registrations of the current thread, checked by tests.
+    pub(crate) static REGISTRATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    /// Number of unregistrations of the current thread, checked by tests.
+    pub(crate) static UNREGISTRATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Register the current thread as a RCU reader.
+pub(crate) fn register_thread() {
+    #[cfg(test)]
+    REGISTRATIONS.with(|cell| cell.set(cell.get() + 1));
+
+    unsafe {
+        urcu_sys::rcu_register_thread();
+    }
+}
+
+/// Unregister the current thread. It must not hold a read lock.
+pub(crate) fn unregister_thread() {
+    #[cfg(test)]
+    UNREGISTRATIONS.with(|cell| cell.set(cell.get() + 1));
+
+    unsafe {
+        urcu_sys::rcu_unregister_thread();
+    }
+}
+
+/// Enter a read-side critical section. Critical sections can be nested.
+pub(crate) fn read_lock() {
+    #[cfg(feature = "memb")]
+    unsafe {
+        urcu_sys::rcu_read_lock();
+    }
+}
+
+/// Leave a read-side critical section.
+pub(crate) fn read_unlock() {
+    #[cfg(feature = "memb")]
+    unsafe {
+        urcu_sys::rcu_read_unlock();
+    }
+}
+
+/// A read-side critical section, left when dropped (also on unwind, from a user callback).
+pub(crate) struct ReadLock;
+
+impl ReadLock {
+    /// Enter a read-side critical section.
+    pub(crate) fn new() -> Self {
+        read_lock();
+        ReadLock
+    }
+}
+
+impl Drop for ReadLock {
+    fn drop(&mut self) {
+        read_unlock();
+    }
+}
+
+/// Wait for a grace period. It must not be called from a read-side critical section.
+pub(crate) fn synchronize() {
+    unsafe {
+        urcu_sys::synchronize_rcu();
+    }
+}
+
+/// Wait until all callbacks queued by `call_rcu` and `defer_rcu` so far are done.
+/// It must not be called from a read-side critical section.
+pub(crate) fn barrier() {
+    unsafe {
+        urcu_sys::rcu_barrier();
+        urcu_sys::rcu_defer_barrier();
+    }
+}
+
+/// Queue `func` to be called with `head` after a grace period.
+pub(crate) unsafe fn call_rcu(
+    head: *mut urcu_sys::rcu_head,
+    func: unsafe extern "C" fn(*mut urcu_sys::rcu_head),
+) {
+    urcu_sys::call_rcu(head, Some(func));
+}
+
+/// Register the current thread for `defer_rcu`, allocating its queue. Returns false on failure.
+pub(crate) fn defer_register_thread() -> bool {
+    unsafe { urcu_sys::rcu_defer_register_thread() == 0 }
+}
+
+/// Unregister the current thread for `defer_rcu`, after running all callbacks of its queue.
+/// It must not be called from a read-side critical section.
+pub(crate) fn defer_unregister_thread() {
+    unsafe {
+        urcu_sys::rcu_defer_unregister_thread();
+    }
+}
+
+/// Queue `func` to be called with `p` after a grace period, in the queue of the current thread.
+/// The thread must be registered with `defer_register_thread`. It waits for a grace period when
+/// the queue is full: it must not be called from a read-side critical section.
+pub(crate) unsafe fn defer_rcu(
+    func: unsafe extern "C" fn(*mut std::os::raw::c_void),
+    p: *mut std::os::raw::c_void,
+) {
+    urcu_sys::defer_rcu(Some(func), p);
+}
+
+/// Report a quiescent state for the current thread (QSBR).
+#[cfg(feature = "qsbr")]
+pub(crate) fn quiescent_state() {
+    unsafe {
+        urcu_sys::rcu_quiescent_state();
+    }
+}
+
+/// Put the current thread offline (QSBR).
+#[cfg(feature = "qsbr")]
+pub(crate) fn thread_offline() {
+    unsafe {
+        urcu_sys::rcu_thread_offline();
+    }
+}
+
+/// Put the current thread back online (QSBR).
+#[cfg(feature = "qsbr")]
+pub(crate) fn thread_online() {
+    unsafe {
+        urcu_sys::rcu_thread_online();
+    }
+}
@@ -8,6 +8,9 @@
 //! Thanks to this implementation, there is no rwlock or mutex in reader threads.
 //! For writer thread, we still need a lock to protect against concurrent insert or remove.
 //!
+//! liburcu 0.11 or newer is required. This is checked at build time by urcu-sys (pkg-config):
+//! liburcu does not export its version, so it cannot be checked again at runtime.
+//!
 //! [liburcu]: http://liburcu.org/
 //! [wyhash]: https://docs.rs/wyhash/0.5.0/wyhash/
 //!
@@ -49,26 +52,96 @@
 //!
 //! child.join().expect("cannot join thread");
 //! ```
+//!
+//! With scoped threads, the hashtable can be shared by reference, without `Arc`:
+//!
+//! ```
+//! use urcu_ht::RcuHt;
+//!
+//! let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+//!
+//! std::thread::scope(|s| {
+//!     s.spawn(|| ht.thread().wrlock().unwrap().insert_or_replace(1, 10));
+//!     s.spawn(|| {
+//!         let thread = ht.thread();
+//!         let _ = thread.rdlock().get(&1);
+//!     });
+//! });
+//! assert_eq!(ht.thread().rdlock().get(&1), Some(&10));
+//! ```
 use std::borrow::Borrow;
 use std::cell::Cell;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 #[cfg(feature = "memb")]
 use std::sync::Once;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+mod flavor;
 
 /// Possible error types returned by this module
 #[derive(Debug)]
 pub enum RcuError {
     /// Impossible to perform the take with provided parameters (allocation of a new hashtable for instance)
     InvalidParameters,
+    /// Hashtable sizes provided at creation are invalid. Message describes which constraint is not met.
+    InvalidSize(&'static str),
     /// Object is not found in hashtable
     NotFound,
     /// Object cannot be free'd. Hopefully, we do not expect this error to appear.
     /// This probably means we have an implementation error which leads to a memory leak.
-    DeleteError(i32),
+    DeleteError(DeleteErrorKind),
+    /// A new hashtable does not behave as expected (not empty), urcu lib is probably not
+    /// initialized or linked properly.
+    InitializationFailed,
+    /// A write mutex is poisoned: a writer panicked while holding it.
+    LockPoisoned,
+}
+
+/// Failure modes of an object deletion (cds_lfht_del).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteErrorKind {
+    /// Object was already removed (or replaced) by someone else.
+    AlreadyRemoved,
+    /// Unexpected error code returned by urcu lib.
+    Other(i32),
+}
+
+impl DeleteErrorKind {
+    /// Classify an error code returned by cds_lfht_del.
+    fn from_code(code: i32) -> Self {
+        match code {
+            code if code == -libc::ENOENT => DeleteErrorKind::AlreadyRemoved,
+            code => DeleteErrorKind::Other(code),
+        }
+    }
+}
+
+impl std::fmt::Display for DeleteErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeleteErrorKind::AlreadyRemoved => write!(f, "object already removed"),
+            DeleteErrorKind::Other(code) => write!(f, "urcu lib error code {}", code),
+        }
+    }
+}
+
+impl std::fmt::Display for RcuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RcuError::InvalidParameters => write!(f, "invalid parameters"),
+            RcuError::InvalidSize(msg) => write!(f, "invalid hashtable size: {}", msg),
+            RcuError::NotFound => write!(f, "object not found in hashtable"),
+            RcuError::DeleteError(kind) => write!(f, "cannot delete object: {}", kind),
+            RcuError::InitializationFailed => write!(f, "urcu lib initialization failed"),
+            RcuError::LockPoisoned => write!(f, "write mutex poisoned"),
+        }
+    }
 }
 
+impl std::error::Error for RcuError {}
+
 // Rcu object is used only to call once urcu lib initialization (urcu_init)
 struct Rcu;
 
@@ -78,25 +151,145 @@ static URCU_LIB_INITIALIZED: Once = Once::new();
 
 impl Rcu {
     pub fn init() {
+        // There is no runtime liburcu version check here: the lib provides no symbol to query it.
+        // Minimum version is enforced when urcu-sys is built.
         #[cfg(feature = "memb")]
-        URCU_LIB_INITIALIZED.call_once(|| unsafe {
-            urcu_sys::rcu_init();
-        });
+        URCU_LIB_INITIALIZED.call_once(flavor::init);
+    }
+}
+
+/// Round `n` to a valid hashtable size: the smallest power of two greater than or equal to `n`.
+///
+/// 0 gives 1, and values above the largest `u64` power of two give that power of two.
+pub fn nearest_power_of_two(n: u64) -> u64 {
+    n.checked_next_power_of_two().unwrap_or(1 << 63)
+}
+
+/// Default hasher of RcuHt: wyhash, with a fixed seed unless configured with [`RcuHtBuilder::seed`].
+#[derive(Clone, Debug)]
+pub struct WyHashBuilder {
+    seed: u64,
+}
+
+impl WyHashBuilder {
+    pub fn with_seed(seed: u64) -> Self {
+        WyHashBuilder { seed }
+    }
+}
+
+impl Default for WyHashBuilder {
+    fn default() -> Self {
+        WyHashBuilder::with_seed(3)
+    }
+}
+
+impl BuildHasher for WyHashBuilder {
+    type Hasher = wyhash::WyHash;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        wyhash::WyHash::with_seed(self.seed)
     }
 }
 
+/// How removed (or replaced) objects are free'd, chosen at creation (see [`RcuHtBuilder::reclaim_mode`]).
+///
+/// Objects can only be free'd once no reader can access them anymore, that is after a grace period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReclaimMode {
+    /// Objects are free'd later from urcu lib's call_rcu thread (call_rcu).
+    /// Writers never wait, but the number of objects waiting to be free'd is not bounded.
+    /// A key or value destructor which panics there aborts the process, as unwinding into urcu
+    /// lib is not possible.
+    #[default]
+    Deferred,
+    /// Writers wait for a grace period (synchronize_rcu), then free objects before returning.
+    /// Memory is bounded, but writes are much slower and must not be done while a read lock is
+    /// held by this thread (it would never return).
+    Synchronous,
+    /// Objects are queued in a per thread queue (defer_rcu), then free'd in batches by urcu lib's
+    /// defer thread. Writers only wait for a grace period when their queue is full: as in
+    /// synchronous mode, writes must not be done while a read lock is held by this thread.
+    /// A key or value destructor which panics aborts the process.
+    DeferRcu,
+}
+
 /// An RcuHt object is an instance of a RCU hashtable.
-pub struct RcuHt<K, V> {
-    /// mutex to protect writer (write operation must be done under lock)
-    mutex: Mutex<RcuHtWriterGuard<K, V>>,
+pub struct RcuHt<K, V, S = WyHashBuilder> {
+    /// mutexes to protect writers (write operation must be done under lock).
+    /// Keys are split in shards by hash, each shard has its own mutex.
+    shards: Vec<Mutex<RcuHtWriterGuard<K, V>>>,
+    /// writers do not lock any mutex (see [`RcuHtBuilder::lock_free_writes`])
+    lock_free_writes: bool,
     /// a pointer to an instance of lib urcu hashtable
     urcuht: *mut urcu_sys::cds_lfht,
+    /// number of buckets requested at creation (or by the last explicit resize).
+    /// Automatic resizes done by urcu lib are not tracked.
+    bucket_count: AtomicU64,
+    /// number of buckets to allocate initially, requested at creation.
+    init_size: u64,
+    /// minimum number of allocated buckets, requested at creation.
+    min_nr_alloc_buckets: u64,
+    /// maximum number of buckets requested at creation (0 means "infinite").
+    max_nr_buckets: u64,
+    /// automatic resize requested at creation.
+    autoresize: bool,
+    /// split counters requested at creation (see [`RcuHtBuilder::accounting`]).
+    accounting: bool,
+    /// how removed objects are free'd.
+    reclaim_mode: ReclaimMode,
+    /// maximum number of objects, oldest ones are evicted above it (see [`RcuHtBuilder::capacity`]).
+    capacity: Option<usize>,
+    /// next insertion sequence number, only used by bounded hashtables.
+    insert_seq: AtomicU64,
+    /// lookup hit and miss counters (see [`RcuHt::hit_miss`]), sharded to avoid contention.
+    #[cfg(feature = "stats")]
+    hit_miss: Box<[HitMissShard]>,
+    /// memory of free'd nodes kept for reuse (see [`RcuHtBuilder::node_pool`]) and allocator of
+    /// nodes (see [`RcuHtBuilder::allocator`]), None if both are disabled.
+    /// It is boxed as nodes keep a pointer to it.
+    pool: Option<Box<NodePool>>,
+    /// number of removed nodes waiting for a grace period to be free'd (see
+    /// [`RcuHt::pending_reclaims`]). It is boxed as nodes keep a pointer to it.
+    pending_reclaims: Box<AtomicUsize>,
+    /// called after writes, see [`RcuHt::on_change`].
+    on_change: Option<ChangeCallback<K, V>>,
+    /// async writers waiting for write mutexes, see [`RcuHtThread::wrlock_async`].
+    #[cfg(feature = "tokio")]
+    async_writers: AsyncWriterQueue,
+    /// used to compute the hash of keys
+    hash_builder: S,
 }
 
+/// Callback registered with [`RcuHt::on_change`].
+type ChangeCallback<K, V> = Box<dyn Fn(&ChangeEvent<'_, K, V>) + Send + Sync>;
+
+/// A successful write, reported to the callback registered with [`RcuHt::on_change`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeEvent<'a, K, V> {
+    /// `key` was inserted, or its value was replaced, with `value`.
+    Insert { key: &'a K, value: &'a V },
+    /// `key` was removed.
+    Remove { key: &'a K },
+}
+
+/// RcuHt can be moved to another thread if its keys and values can.
+///
+/// ```compile_fail
+/// let ht = urcu_ht::RcuHt::<u32, std::rc::Rc<u32>>::new(64, 64, 64, false).unwrap();
+/// std::thread::spawn(move || drop(ht));
+/// ```
+unsafe impl<K: Send, V: Send, S: Send> Send for RcuHt<K, V, S> {}
 /// RcuHt can be shared between threads (under std::sync::Arc<>).
-unsafe impl<K, V> Send for RcuHt<K, V> {}
-/// RcuHt can be shared between threads (under std::sync::Arc<>).
-unsafe impl<K, V> Sync for RcuHt<K, V> {}
+///
+/// Objects are inserted from a thread, read from others, and free'd from urcu lib's call_rcu thread:
+/// keys and values must be both Send and Sync.
+///
+/// ```compile_fail
+/// let ht = urcu_ht::RcuHt::<u32, std::cell::Cell<u32>>::new(64, 64, 64, false).unwrap();
+/// let ht = std::sync::Arc::new(ht);
+/// std::thread::spawn(move || drop(ht));
+/// ```
+unsafe impl<K: Send + Sync, V: Send + Sync, S: Sync> Sync for RcuHt<K, V, S> {}
 
 impl<K, V> RcuHt<K, V>
 where
@@ -111,21 +304,178 @@ where
     /// @min_nr_alloc_buckets: the minimum number of allocated buckets. (must be power of two).
     ///
     /// @max_nr_buckets: the maximum number of hash table buckets allowed. (must be power of two, 0 is accepted, means "infinite").
+    /// It must not be lower than init_size and min_nr_alloc_buckets.
     ///
     /// @autoresize: automatically resize hash table.
+    ///
+    /// See [`RcuHtBuilder`] for a less error-prone way to create a hashtable.
     pub fn new(
         init_size: u64,
         min_nr_alloc_buckets: u64,
         max_nr_buckets: u64,
         autoresize: bool,
     ) -> Result<Self, RcuError> {
+        Self::with_hasher(
+            init_size,
+            min_nr_alloc_buckets,
+            max_nr_buckets,
+            autoresize,
+            WyHashBuilder::default(),
+        )
+    }
+
+    /// Allocate a new hashtable sized for roughly `capacity` objects, with automatic resize.
+    ///
+    /// The bucket array starts at `capacity` rounded up to a power of two, never shrinks below it,
+    /// and grows without limit. The number of objects is not bounded: see [`RcuHtBuilder::capacity`]
+    /// for a bounded hashtable.
+    pub fn with_capacity(capacity: usize) -> Result<Self, RcuError> {
+        let size = nearest_power_of_two(capacity as u64);
+        Self::new(size, size, 0, true)
+    }
+
+    /// Adopt an existing urcu hashtable, created by C code for instance, whose nodes are hashed
+    /// with the default hasher (wyhash, default seed).
+    ///
+    /// See [`from_raw_with_hasher`](RcuHt::from_raw_with_hasher) for other hashers.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`from_raw_with_hasher`](RcuHt::from_raw_with_hasher), with nodes hashed by
+    /// `WyHashBuilder::default()`.
+    pub unsafe fn from_raw(ptr: *mut urcu_sys::cds_lfht) -> Self {
+        Self::from_raw_with_hasher(ptr, WyHashBuilder::default())
+    }
+}
+
+impl<K, V, S> RcuHt<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Adopt an existing urcu hashtable, created by C code or given up by [`RcuHt::into_raw`] for
+    /// instance, using `hash_builder` to hash keys.
+    ///
+    /// Parameters used to create it are unknown: the hashtable reports 1 bucket initially
+    /// (and at least), no maximum and no automatic resize, and bucket counts are only tracked
+    /// from explicit resizes done from now on. It gets a single writer shard and the default
+    /// configuration of [`RcuHtBuilder`] otherwise.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` must be a valid hashtable, allocated with `cds_lfht_new` for the RCU flavor this
+    ///   crate is built with, and not used by anything else than the returned object from now on
+    ///   (it is destroyed when that object is dropped).
+    /// - All nodes of this hashtable must be `RcuLfhtNode<K, V>` structs, with exactly the same
+    ///   key and value types: a `cds_lfht_node` and a `rcu_head` first, then an insertion sequence
+    ///   number (any value), a node pool pointer, a pending reclaims counter pointer, an
+    ///   insertion time (`ttl` feature only), the key and the value. Rust field order and layout
+    ///   rules apply (`#[repr(C)]`).
+    /// - Both pointers must be null, unless nodes were inserted by a hashtable given up with
+    ///   [`RcuHt::into_raw`]: it leaks the node pool and counter they point to.
+    /// - Nodes without a node pool must be allocated by the Rust global allocator with the layout
+    ///   of this struct, as they are free'd by it.
+    /// - Nodes must be hashed with `hash_builder`: for instance a clone of the hasher of the
+    ///   hashtable given up with [`RcuHt::into_raw`].
+    pub unsafe fn from_raw_with_hasher(ptr: *mut urcu_sys::cds_lfht, hash_builder: S) -> Self {
+        // initialize global lib if not already done
+        Rcu::init();
+
+        RcuHt {
+            urcuht: ptr,
+            shards: vec![Mutex::new(RcuHtWriterGuard::new())],
+            lock_free_writes: false,
+            bucket_count: AtomicU64::new(1),
+            init_size: 1,
+            min_nr_alloc_buckets: 1,
+            max_nr_buckets: 0,
+            autoresize: false,
+            accounting: false,
+            reclaim_mode: ReclaimMode::Deferred,
+            capacity: None,
+            insert_seq: AtomicU64::new(0),
+            #[cfg(feature = "stats")]
+            hit_miss: (0..HIT_MISS_SHARDS).map(|_| Default::default()).collect(),
+            pool: None,
+            pending_reclaims: Box::new(AtomicUsize::new(0)),
+            on_change: None,
+            #[cfg(feature = "tokio")]
+            async_writers: AsyncWriterQueue::default(),
+            hash_builder,
+        }
+    }
+
+    /// Allocate a new instance of urcu hashtable, using `hash_builder` to hash keys.
+    ///
+    /// Other parameters are the same as [`RcuHt::new`].
+    pub fn with_hasher(
+        init_size: u64,
+        min_nr_alloc_buckets: u64,
+        max_nr_buckets: u64,
+        autoresize: bool,
+        hash_builder: S,
+    ) -> Result<Self, RcuError> {
+        Self::create(
+            init_size,
+            min_nr_alloc_buckets,
+            max_nr_buckets,
+            autoresize,
+            false,
+            hash_builder,
+        )
+    }
+
+    /// helper function to allocate a new instance of urcu hashtable, with split counters if
+    /// `accounting` is true (see [`RcuHtBuilder::accounting`]).
+    fn create(
+        init_size: u64,
+        min_nr_alloc_buckets: u64,
+        max_nr_buckets: u64,
+        autoresize: bool,
+        accounting: bool,
+        hash_builder: S,
+    ) -> Result<Self, RcuError> {
+        if !init_size.is_power_of_two() {
+            return Err(RcuError::InvalidSize("init_size must be a power of two"));
+        }
+
+        if !min_nr_alloc_buckets.is_power_of_two() {
+            return Err(RcuError::InvalidSize(
+                "min_nr_alloc_buckets must be a power of two",
+            ));
+        }
+
+        if max_nr_buckets != 0 {
+            if !max_nr_buckets.is_power_of_two() {
+                return Err(RcuError::InvalidSize(
+                    "max_nr_buckets must be a power of two (or 0)",
+                ));
+            }
+
+            if min_nr_alloc_buckets > max_nr_buckets {
+                return Err(RcuError::InvalidSize(
+                    "min_nr_alloc_buckets must not be greater than max_nr_buckets",
+                ));
+            }
+
+            // urcu lib would silently use max_nr_buckets initially
+            if init_size > max_nr_buckets {
+                return Err(RcuError::InvalidSize(
+                    "init_size must not be greater than max_nr_buckets",
+                ));
+            }
+        }
+
         // initialize global lib if not already done
         Rcu::init();
 
-        let flags: i32 = match autoresize {
+        let mut flags: i32 = match autoresize {
             true => urcu_sys::CDS_LFHT_AUTO_RESIZE as i32,
             false => 0,
         };
+        if accounting {
+            flags |= CDS_LFHT_ACCOUNTING;
+        }
 
         unsafe {
             let urcuht = urcu_sys::cds_lfht_new(
@@ -140,550 +490,3897 @@ where
                 return Err(RcuError::InvalidParameters);
             }
 
-            let mutex = Mutex::new(RcuHtWriterGuard::new());
+            if !urcu_sanity_check(urcuht) {
+                urcu_sys::cds_lfht_destroy(urcuht, std::ptr::null_mut());
+                return Err(RcuError::InitializationFailed);
+            }
+
+            let shards = vec![Mutex::new(RcuHtWriterGuard::new())];
+
+            // init_size was checked above to be a valid number of buckets for urcu lib
+            let bucket_count = AtomicU64::new(init_size);
 
-            Ok(RcuHt { urcuht, mutex })
+            Ok(RcuHt {
+                urcuht,
+                shards,
+                lock_free_writes: false,
+                bucket_count,
+                init_size,
+                min_nr_alloc_buckets,
+                max_nr_buckets,
+                autoresize,
+                accounting,
+                reclaim_mode: ReclaimMode::Deferred,
+                capacity: None,
+                insert_seq: AtomicU64::new(0),
+                #[cfg(feature = "stats")]
+                hit_miss: (0..HIT_MISS_SHARDS).map(|_| Default::default()).collect(),
+                pool: None,
+                pending_reclaims: Box::new(AtomicUsize::new(0)),
+                on_change: None,
+                #[cfg(feature = "tokio")]
+                async_writers: AsyncWriterQueue::default(),
+                hash_builder,
+            })
         }
     }
 
-    /// Get a per thread handle. Will be used for read/write operations.
-    pub fn thread(&self) -> RcuHtThread<K, V> {
-        RcuHtThread::new(self.urcuht, &self.mutex)
+    /// Number of buckets to allocate initially, as requested at creation.
+    pub fn init_size(&self) -> u64 {
+        self.init_size
     }
-}
 
-impl<K, V> Drop for RcuHt<K, V> {
-    /// Release an instance of a RCU hashtable.
-    fn drop(&mut self) {
-        unsafe {
-            // must be called when there is no more writer or reader able to access this hashtable.
-            // XXX should probably be empty before free ???
-            urcu_sys::cds_lfht_destroy(self.urcuht, std::ptr::null_mut());
-        }
+    /// Minimum number of allocated buckets, as requested at creation.
+    pub fn min_buckets(&self) -> u64 {
+        self.min_nr_alloc_buckets
     }
-}
 
-/// This describes every object stored in hashtable.
-#[repr(C)]
-struct RcuLfhtNode<K, V> {
-    /// internal node to link to other objects it in hashtable
-    node: urcu_sys::cds_lfht_node,
-    /// data structure used for delayed free
-    head: urcu_sys::rcu_head,
-    /// object key (user data)
-    key: K,
-    /// object data (user data)
-    data: V,
-}
+    /// Maximum number of buckets, as requested at creation. 0 means "infinite".
+    pub fn max_buckets(&self) -> u64 {
+        self.max_nr_buckets
+    }
 
-/// Match function callback used when looking for objects.
-/// Returns 1 if current node key and lookup key are equals, 0 otherwise.
-/// Called by urcu_sys::cds_lfht_lookup
-/// Unsized callback version
-unsafe extern "C" fn urcu_match_ref_fn<Q, K, V>(
-    node: *mut urcu_sys::cds_lfht_node,
-    key: *const std::ffi::c_void,
-) -> i32
-where
-    K: Borrow<Q>,
-    Q: ?Sized + Eq,
-{
-    let key = *(key as *const &Q);
-    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(node);
-    let node_key = (*node).key.borrow();
+    /// Returns true if hashtable was created with automatic resize.
+    pub fn autoresize_enabled(&self) -> bool {
+        self.autoresize
+    }
 
-    if key.eq(node_key) {
-        1
-    } else {
-        0
+    /// Returns true if hashtable was created with split counters, see [`RcuHtBuilder::accounting`].
+    pub fn accounting_enabled(&self) -> bool {
+        self.accounting
     }
-}
 
-/// Match function callback used when looking for objects.
-/// Returns 1 if current node key and lookup key are equals, 0 otherwise.
-/// Called by urcu_sys::cds_lfht_lookup
-/// Sized callback version
-unsafe extern "C" fn urcu_match_fn<K, V>(
-    node: *mut urcu_sys::cds_lfht_node,
-    key: *const std::ffi::c_void,
-) -> i32
-where
-    K: Eq,
-{
-    let key = key as *const K;
-    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(node);
+    /// How removed objects are free'd, see [`RcuHtBuilder::reclaim_mode`].
+    pub fn reclaim_mode(&self) -> ReclaimMode {
+        self.reclaim_mode
+    }
 
-    if (*key).eq(&(*node).key) {
-        1
-    } else {
-        0
+    /// Maximum number of objects, see [`RcuHtBuilder::capacity`]. None if not bounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
     }
-}
 
-/// Get back to RcuLfhtNode rust type from a "node" pointer. C-like dark pointer operations and casting.
-unsafe fn urcu_cds_lfht_node_to_rust_type<K, V>(
-    node: *mut urcu_sys::cds_lfht_node,
-) -> *mut RcuLfhtNode<K, V> {
-    let offset = memoffset::offset_of!(RcuLfhtNode::<K, V>, node);
+    /// Get the pointer to the underlying urcu hashtable, still owned by this object.
+    pub fn as_raw(&self) -> *mut urcu_sys::cds_lfht {
+        self.urcuht
+    }
 
-    let ptr = node.cast::<u8>();
-    ptr.sub(offset).cast::<RcuLfhtNode<K, V>>()
-}
+    /// Give up ownership of the underlying urcu hashtable, and return it with all its objects.
+    ///
+    /// The caller is responsible for destroying it, for instance with
+    /// [`RcuHt::from_raw_with_hasher`] and a clone of [`hasher`](Self::hasher) (or
+    /// [`RcuHt::from_raw`] for the default one). No reader or writer can be alive at this time
+    /// (they borrow this object). The node pool, if any, is leaked: remaining nodes may still give
+    /// their memory back to it (or to its allocator). So is the pending reclaims counter.
+    pub fn into_raw(self) -> *mut urcu_sys::cds_lfht {
+        let this = std::mem::ManuallyDrop::new(self);
+        // every field is listed: a new one cannot be forgotten (and leaked) here
+        let RcuHt {
+            shards,
+            lock_free_writes: _,
+            urcuht,
+            bucket_count: _,
+            init_size: _,
+            min_nr_alloc_buckets: _,
+            max_nr_buckets: _,
+            autoresize: _,
+            accounting: _,
+            reclaim_mode: _,
+            capacity: _,
+            insert_seq: _,
+            #[cfg(feature = "stats")]
+            hit_miss,
+            pool,
+            pending_reclaims,
+            on_change,
+            // no memory to release: tokio's mutex and notify are not boxed
+            #[cfg(feature = "tokio")]
+                async_writers: _,
+            hash_builder,
+        } = &*this;
 
-/// Get back to RcuLfhtNode rust type from a "head" pointer. C-like dark pointer operations and casting.
-unsafe fn urcu_cds_lfht_head_to_rust_type<K, V>(
-    node: *mut urcu_sys::rcu_head,
-) -> *mut RcuLfhtNode<K, V> {
-    let offset = memoffset::offset_of!(RcuLfhtNode::<K, V>, head);
+        // release all other resources, without destroying the urcu hashtable
+        unsafe {
+            drop(std::ptr::read(shards));
+            drop(std::ptr::read(hash_builder));
+            #[cfg(feature = "stats")]
+            drop(std::ptr::read(hit_miss));
+            std::mem::forget(std::ptr::read(pool));
+            std::mem::forget(std::ptr::read(pending_reclaims));
+            drop(std::ptr::read(on_change));
+        }
 
-    let ptr = node.cast::<u8>();
-    ptr.sub(offset).cast::<RcuLfhtNode<K, V>>()
-}
+        *urcuht
+    }
 
-/// Helper function used to perform lookup (used at multiple places).
-/// This function must be called with rcu_read_lock held.
-/// Threads calling this API need to be registered (urcu_sys::rcu_register_thread).
-unsafe fn urcu_get_node<Q, K, V>(
-    ht: *mut urcu_sys::cds_lfht,
-    key: &Q,
-) -> *mut urcu_sys::cds_lfht_node
-where
-    K: Borrow<Q>,
-    Q: ?Sized + Hash + Eq,
-{
-    let hash = urcu_key_hash(key);
+    /// Get the number of lookups which found an object (hits) and which did not (misses), since
+    /// the hashtable creation, when `stats` feature is enabled.
+    ///
+    /// Counters are sharded by thread and updated with relaxed atomics, so the read path is not
+    /// slowed down by contention: the result is approximate when readers are running.
+    #[cfg(feature = "stats")]
+    pub fn hit_miss(&self) -> (u64, u64) {
+        self.hit_miss.iter().fold((0, 0), |(hits, misses), shard| {
+            (
+                hits + shard.hits.load(Ordering::Relaxed),
+                misses + shard.misses.load(Ordering::Relaxed),
+            )
+        })
+    }
 
-    let mut iter: urcu_sys::cds_lfht_iter = std::mem::MaybeUninit::uninit().assume_init();
+    /// helper function to count a lookup hit or miss.
+    #[cfg(feature = "stats")]
+    fn record_hit_miss(&self, hit: bool) {
+        let shard = &self.hit_miss[urcu_hit_miss_shard()];
+        match hit {
+            true => shard.hits.fetch_add(1, Ordering::Relaxed),
+            false => shard.misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
 
-    // cds_lfht_lookup - lookup a node by key.
-    // @ht: the hash table.
-    // @hash: the key hash.
-    // @match: the key match function.
-    // @key: the current node key.
-    // @iter: node, if found (output). *iter->node set to NULL if not found.
-    // This function acts as a rcu_dereference() to read the node pointer.
-    urcu_sys::cds_lfht_lookup(
-        ht,
-        hash,
-        Some(urcu_match_ref_fn::<Q, K, V>),
-        &key as *const &Q as *const std::ffi::c_void,
-        &mut iter as *mut urcu_sys::cds_lfht_iter,
-    );
+    /// Get the hasher used to compute the hash of keys.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
 
-    let found_node: *mut urcu_sys::cds_lfht_node = urcu_sys::cds_lfht_iter_get_node(&mut iter);
+    /// Number of removed (or replaced) objects waiting for a grace period to be free'd.
+    ///
+    /// A growing number means readers are stalling grace periods. Only objects free'd in
+    /// [`ReclaimMode::Deferred`] and [`ReclaimMode::DeferRcu`] modes are counted.
+    pub fn pending_reclaims(&self) -> usize {
+        self.pending_reclaims.load(Ordering::Relaxed)
+    }
 
-    found_node
-}
+    /// Maximum number of free'd nodes kept for reuse, see [`RcuHtBuilder::node_pool`].
+    pub fn node_pool_capacity(&self) -> usize {
+        self.pool.as_ref().map_or(0, |pool| pool.capacity)
+    }
 
-/// helper function to compute a hash of a key.
-fn urcu_key_hash<K: ?Sized + Hash>(data: &K) -> u64 {
-    let mut hasher = wyhash::WyHash::with_seed(3);
-    data.hash(&mut hasher);
-    hasher.finish()
-}
+    /// Get a per thread handle. Will be used for read/write operations.
+    ///
+    /// The thread is registered in urcu lib by its first handle, and unregistered when its last
+    /// handle is dropped. Getting (or cloning) a handle while another one is alive on the same
+    /// thread is cheap: it only increments a thread local counter. Long-lived worker threads
+    /// (thread pools) should keep one handle for their whole life, so that handles obtained later
+    /// do not register the thread again.
+    pub fn thread(&self) -> RcuHtThread<'_, K, V, S> {
+        RcuHtThread::new(self)
+    }
 
-/// Callback function, called after some delay, when it is time to free a node.
-unsafe extern "C" fn urcu_free_node<K, V>(head: *mut urcu_sys::rcu_head)
-where
-    K: Hash + Eq,
-{
-    let node = urcu_cds_lfht_head_to_rust_type::<K, V>(head);
+    /// Register `f`, to be called for each object successfully inserted, replaced or removed
+    /// (including capacity evictions), for instance to mirror writes to another store. A key
+    /// deleted with all its duplicates by [`RcuHtWriter::remove`] is reported once.
+    ///
+    /// `f` must not use this hashtable. Writes of an [`RcuHtWriter`] are reported while its lock
+    /// is held, so they are serialized. With [`RcuHtBuilder::lock_free_writes`], inserts are
+    /// reported just before they are published, and events of concurrent writers may come in any
+    /// order. Dropping (or consuming) the hashtable is not reported. `f` is not copied by `clone`.
+    ///
+    /// ```
+    /// let ht = urcu_ht::RcuHtBuilder::new()
+    ///     .build::<u32, u32>()
+    ///     .unwrap()
+    ///     .on_change(|event| println!("{:?}", event));
+    /// ht.thread().wrlock().unwrap().insert_or_replace(1, 1);
+    /// ```
+    pub fn on_change<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ChangeEvent<'_, K, V>) + Send + Sync + 'static,
+    {
+        self.on_change = Some(Box::new(f));
+        self
+    }
 
-    std::ptr::drop_in_place(&mut (*node).key);
-    std::ptr::drop_in_place(&mut (*node).data);
+    /// helper function to record a successful write: every write path reports it here, for
+    /// metrics (`metrics` feature) and to the registered change callback, if any.
+    fn notify_change(&self, event: &ChangeEvent<'_, K, V>) {
+        urcu_metrics_record(match event {
+            ChangeEvent::Insert { .. } => "insert",
+            ChangeEvent::Remove { .. } => "remove",
+        });
 
-    let layout = std::alloc::Layout::new::<RcuLfhtNode<K, V>>();
-    std::alloc::dealloc(node as *mut u8, layout);
-}
+        if let Some(on_change) = &self.on_change {
+            on_change(event);
+        }
+    }
 
-// thread local flag for thread register / unregister
-// since this is a local thread storage, there is no concurrency, so no need for atomics
-thread_local! {
-    static URCU_THREAD_REGISTERED_COUNT: Cell<u32>  = Cell::new(0);
-}
+    /// helper function to report an inserted (or replacing) node to the change callback, if any.
+    /// The node must not be free'd meanwhile.
+    unsafe fn notify_insert(&self, node: *const RcuLfhtNode<K, V>) {
+        self.notify_change(&ChangeEvent::Insert {
+            key: &(*node).key,
+            value: &(*node).data,
+        });
+    }
 
-/// Per thread object used to provide safe access to RCU hashtable.
-///
-/// It registers the current thread if needed (the first reader or writer object triggers the registration).
-/// It unregisters the current thread when no more objects are alive in this thread.
-pub struct RcuHtThread<'ht, K, V> {
-    urcuht: *mut urcu_sys::cds_lfht,
-    mutex: &'ht Mutex<RcuHtWriterGuard<K, V>>,
-}
-
-impl<'ht, K, V> RcuHtThread<'ht, K, V>
-where
-    K: Hash + Eq,
-{
-    /// Get a new "read" handle.
-    /// A different handle is needed for each thread doing "read" operations.
-    /// It registers this thread in urcu lib.
-    /// It must stick to a single thread. One must not try to move this handle between threads.
-    pub fn new(urcuht: *mut urcu_sys::cds_lfht, mutex: &'ht Mutex<RcuHtWriterGuard<K, V>>) -> Self {
-        // manage thread reference counter : if the count is 1 => register this thread
-        let thread_count = URCU_THREAD_REGISTERED_COUNT.with(|cell| {
-            let mut thread_count = cell.get();
-            thread_count += 1;
-            cell.set(thread_count);
-            thread_count
-        });
+    /// helper function to report a deleted node to the change callback, if any.
+    /// The node must not be free'd meanwhile.
+    unsafe fn notify_remove(&self, node: *const RcuLfhtNode<K, V>) {
+        self.notify_change(&ChangeEvent::Remove { key: &(*node).key });
+    }
 
-        if thread_count == 1 {
-            unsafe {
-                urcu_sys::rcu_register_thread();
-            }
-        }
+    /// Copy all objects of the hashtable into an owned, immutable snapshot.
+    ///
+    /// Unlike [`RcuHtRead::to_hashmap`], the copy is consistent: all writer shards are locked
+    /// while every key and value is cloned (O(n)), so writers are blocked for the whole copy.
+    /// Readers are not. Returns None in the same cases as [`RcuHtThread::wrlock`].
+    pub fn snapshot(&self) -> Option<RcuHtSnapshot<K, V>>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let thread = self.thread();
+        let _wrlock = thread.wrlock()?;
+        let map = thread.rdlock().to_hashmap();
 
-        // return an object with a pointer to the hashtable
-        // Return an object with a reference to a shared write mutex for this hashtable.
-        RcuHtThread {
-            urcuht,
-            // This prevent concurrent write on this hashtable.
-            // Since mutex is a reference, we are sure original hashtable cannot be deleted before this object.
-            // This is needed to protect hashtable deletion.
-            mutex,
-        }
+        Some(RcuHtSnapshot(map))
     }
 
-    pub fn wrlock(&self) -> Option<RcuHtWriter<K, V>> {
-        match self.mutex.lock() {
-            Ok(guard) => Some(RcuHtWriter::new(self.urcuht, self, guard)),
-            Err(_err) => None,
-        }
+    /// Number of writer shards, see [`RcuHtBuilder::writer_shards`].
+    pub fn writer_shards(&self) -> usize {
+        self.shards.len()
     }
 
-    pub fn rdlock(&self) -> RcuHtRead<K, V> {
-        RcuHtRead::new(self.urcuht, self)
+    /// Returns true if hashtable was built with lock free writes, see [`RcuHtBuilder::lock_free_writes`].
+    pub fn lock_free_writes_enabled(&self) -> bool {
+        self.lock_free_writes
     }
 
-    #[cfg(feature = "qsbr")]
-    pub fn quiescent_state(&self) {
-        unsafe {
-            urcu_sys::rcu_quiescent_state();
-        }
+    /// helper function to compute a hash of a key.
+    fn key_hash<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
+        self.hash_builder.hash_one(key)
     }
-}
 
-impl<'ht, K, V> Drop for RcuHtThread<'ht, K, V> {
-    fn drop(&mut self) {
-        /* manage thread reference counter : if the count is 0 (last object) => unregister this thread */
-        let thread_count = URCU_THREAD_REGISTERED_COUNT.with(|cell| {
-            let mut thread_count = cell.get();
-            thread_count -= 1;
-            cell.set(thread_count);
-            thread_count
-        });
+    /// helper function to get the writer shard of a key hash.
+    fn shard_of(&self, hash: u64) -> usize {
+        (hash % self.shards.len() as u64) as usize
+    }
 
-        if thread_count == 0 {
-            unsafe {
-                urcu_sys::rcu_unregister_thread();
+    /// helper function to allocate a new node (from the node pool, if any).
+    /// Abort on allocation failure, as standard collections do.
+    unsafe fn alloc_node(&self, key: K, data: V) -> *mut RcuLfhtNode<K, V> {
+        match self.try_alloc_node(key, data) {
+            Ok(val) => val,
+            Err(_) => {
+                std::alloc::handle_alloc_error(std::alloc::Layout::new::<RcuLfhtNode<K, V>>())
             }
         }
     }
-}
 
-fn urcu_read_lock() {
-    #[cfg(feature = "memb")]
-    unsafe {
-        urcu_sys::rcu_read_lock();
-    }
-}
+    /// helper function to allocate a new node (from the node pool, if any).
+    /// It gets an insertion sequence number if hashtable is bounded.
+    /// Give back key and data on allocation failure.
+    unsafe fn try_alloc_node(&self, key: K, data: V) -> Result<*mut RcuLfhtNode<K, V>, (K, V)> {
+        let pool = match &self.pool {
+            Some(pool) => &**pool as *const NodePool,
+            None => std::ptr::null(),
+        };
 
-fn urcu_read_unlock() {
-    #[cfg(feature = "memb")]
-    unsafe {
-        urcu_sys::rcu_read_unlock();
-    }
-}
+        let val = urcu_try_alloc_node(key, data, pool)?;
+        (*val).pending_reclaims = &*self.pending_reclaims;
 
-pub struct RcuHtRead<'thread, 'ht, K, V> {
-    urcuht: *mut urcu_sys::cds_lfht,
-    _thread: &'thread RcuHtThread<'ht, K, V>,
-}
+        if self.capacity.is_some() {
+            (*val).seq = self.insert_seq.fetch_add(1, Ordering::Relaxed);
+        }
 
-impl<'rdlock, 'thread, 'ht, K, V> RcuHtRead<'thread, 'ht, K, V>
-where
-    K: Hash + Eq,
-{
-    /// Get a new "read" handle.
-    /// A different handle is needed for each thread doing "read" operations.
-    /// It registers this thread in urcu lib.
-    /// It must stick to a single thread. One must not try to move this handle between threads.
-    pub fn new(urcuht: *mut urcu_sys::cds_lfht, thread: &'thread RcuHtThread<'ht, K, V>) -> Self {
-        urcu_read_lock();
+        Ok(val)
+    }
 
-        RcuHtRead {
-            urcuht,
-            _thread: thread,
+    /// helper function to evict oldest objects after an insert, if hashtable is bounded.
+    /// Must be called outside of a read-side critical section.
+    unsafe fn evict_over_capacity(&self) {
+        if let Some(capacity) = self.capacity {
+            urcu_evict_oldest::<K, V>(self.urcuht, capacity, self.reclaim_mode, |key| {
+                self.notify_change(&ChangeEvent::Remove { key })
+            });
         }
     }
+}
 
-    pub fn get<Q: ?Sized>(&'rdlock self, key: &Q) -> Option<&'rdlock V>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
-    {
-        let mut ret: Option<&V> = None;
-
-        unsafe {
-            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, key);
+/// Builder used to configure a new hashtable.
+///
+/// Default configuration: 64 buckets initially (and at least), no maximum, automatic resize, wyhash.
+///
+/// ```
+/// let ht = urcu_ht::RcuHtBuilder::new()
+///     .max_buckets(1024)
+///     .build::<u32, u32>()
+///     .unwrap();
+/// assert!(ht.autoresize_enabled());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RcuHtBuilder<S = WyHashBuilder> {
+    init_size: u64,
+    min_nr_alloc_buckets: u64,
+    max_nr_buckets: u64,
+    autoresize: bool,
+    accounting: bool,
+    writer_shards: usize,
+    lock_free_writes: bool,
+    reclaim_mode: ReclaimMode,
+    capacity: Option<usize>,
+    node_pool: usize,
+    allocator: Option<NodeAllocator>,
+    hash_builder: S,
+}
 
-            if !found_node.is_null() {
-                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
-                ret = Some(&(*node).data);
-            }
+impl RcuHtBuilder {
+    pub fn new() -> Self {
+        RcuHtBuilder {
+            init_size: 64,
+            min_nr_alloc_buckets: 64,
+            max_nr_buckets: 0,
+            autoresize: true,
+            accounting: false,
+            writer_shards: 1,
+            lock_free_writes: false,
+            reclaim_mode: ReclaimMode::Deferred,
+            capacity: None,
+            node_pool: 0,
+            allocator: None,
+            hash_builder: WyHashBuilder::default(),
         }
+    }
 
-        ret
+    /// Use wyhash with this `seed` to hash keys.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.hash_builder = WyHashBuilder::with_seed(seed);
+        self
     }
 }
 
-impl<'thread, 'ht, K, V> Drop for RcuHtRead<'thread, 'ht, K, V> {
-    fn drop(&mut self) {
-        urcu_read_unlock();
+impl Default for RcuHtBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub struct RcuHtWriterGuard<K, V> {
-    phantom_key: PhantomData<K>,
-    phantom_val: PhantomData<V>,
-}
+impl<S> RcuHtBuilder<S> {
+    /// Number of buckets to allocate initially. Must be power of two.
+    pub fn init_size(mut self, init_size: u64) -> Self {
+        self.init_size = init_size;
+        self
+    }
 
-impl<K, V> RcuHtWriterGuard<K, V> {
-    fn new() -> Self {
-        RcuHtWriterGuard {
-            phantom_key: PhantomData,
-            phantom_val: PhantomData,
-        }
+    /// Minimum number of allocated buckets. Must be power of two.
+    pub fn min_buckets(mut self, min_nr_alloc_buckets: u64) -> Self {
+        self.min_nr_alloc_buckets = min_nr_alloc_buckets;
+        self
     }
-}
 
-/// Writer object used to perform safe add and del operations.
-///
-/// It can only be called under locked mutex to protect from concurrent access.
-/// It must not be shared between threads.
-pub struct RcuHtWriter<'guard, 'thread, 'ht, K, V> {
-    urcuht: *mut urcu_sys::cds_lfht,
-    // keep references to thread so object cannot be destroyed in an invalid order
-    _thread: &'thread RcuHtThread<'ht, K, V>,
-    // have the guard here so lock will be released when writer is destroyed
-    _guard: MutexGuard<'guard, RcuHtWriterGuard<K, V>>,
-}
+    /// Maximum number of buckets. Must be power of two, 0 means "infinite".
+    pub fn max_buckets(mut self, max_nr_buckets: u64) -> Self {
+        self.max_nr_buckets = max_nr_buckets;
+        self
+    }
 
-impl<'guard, 'thread, 'ht, K, V> RcuHtWriter<'guard, 'thread, 'ht, K, V>
-where
-    K: Hash + Eq,
-{
-    /// Creates a write instance.
+    /// Automatically resize hash table.
+    pub fn autoresize(mut self, autoresize: bool) -> Self {
+        self.autoresize = autoresize;
+        self
+    }
+
+    /// Count additions and removals in per-CPU split counters (urcu lib CDS_LFHT_ACCOUNTING),
+    /// reported by [`RcuHtRead::split_counts`]. This costs an atomic operation per write.
+    pub fn accounting(mut self, accounting: bool) -> Self {
+        self.accounting = accounting;
+        self
+    }
+
+    /// Split keys in `writer_shards` shards (by hash), each one protected by its own write mutex.
     ///
-    /// There should be only one single instance allocated under the write mutex.
-    fn new(
-        urcuht: *mut urcu_sys::cds_lfht,
-        thread: &'thread RcuHtThread<'ht, K, V>,
-        guard: MutexGuard<'guard, RcuHtWriterGuard<K, V>>,
-    ) -> RcuHtWriter<'guard, 'thread, 'ht, K, V> {
-        // return an object containing the pointer to the hashtable
-        RcuHtWriter {
-            urcuht,
-            _thread: thread,
-            _guard: guard,
-        }
+    /// Writers of different shards, obtained with [`RcuHtThread::wrlock_for`], can run concurrently.
+    /// Default is a single shard. Must not be 0.
+    pub fn writer_shards(mut self, writer_shards: usize) -> Self {
+        self.writer_shards = writer_shards;
+        self
     }
 
-    /// Add or replace an existing key/value.
+    /// Let writers run concurrently, without any write mutex.
     ///
-    /// Parameters (key and value) are moved in hashtable.
-    /// Main difference with standard collection HashMap : we cannot return/move existing value here.
-    /// We must destroy it after a grace period. If it were returned by this function, it could be deleted immediately
-    pub fn insert_or_replace(&mut self, key: K, value: V) {
-        let h = urcu_key_hash(&key);
+    /// urcu lib add and del operations are safe for concurrent use. In this mode, writes are only
+    /// done with [`RcuHtConcurrentWriter`] ([`RcuHtThread::concurrent_writer`]), which provides
+    /// per-key operations only. Mutex based writers ([`RcuHtThread::wrlock`]) are not available.
+    pub fn lock_free_writes(mut self, lock_free_writes: bool) -> Self {
+        self.lock_free_writes = lock_free_writes;
+        self
+    }
 
-        let layout = std::alloc::Layout::new::<RcuLfhtNode<K, V>>();
+    /// Choose how removed (or replaced) objects are free'd. Default is [`ReclaimMode::Deferred`].
+    ///
+    /// [`ReclaimMode::Synchronous`] trades write throughput for a bounded memory usage.
+    pub fn reclaim_mode(mut self, reclaim_mode: ReclaimMode) -> Self {
+        self.reclaim_mode = reclaim_mode;
+        self
+    }
 
-        unsafe {
-            /* allocate a new RcuLfhtNode to store data */
-            /* alloc style from https://doc.rust-lang.org/nomicon/vec/vec-alloc.html */
+    /// Bound the hashtable to `capacity` objects, turning it into a concurrent bounded cache.
+    ///
+    /// Once an insert leaves more than `capacity` objects, the oldest ones (by insertion order)
+    /// are evicted, as if removed. Finding them walks the whole hashtable on each insert.
+    /// Default is unbounded.
+    ///
+    /// Must not be 0. A bounded hashtable cannot have several writer shards, nor lock free writes:
+    /// a writer evicts objects of any key, which must not happen while another writer references
+    /// them.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
 
-            let ptr = std::alloc::alloc(layout);
+    /// Keep memory of up to `node_pool` free'd nodes, to reuse it for new objects.
+    ///
+    /// Workloads inserting and removing many objects then mostly avoid the system allocator.
+    /// Memory kept in the pool is only given back when the hashtable is dropped. Default is 0
+    /// (disabled).
+    pub fn node_pool(mut self, node_pool: usize) -> Self {
+        self.node_pool = node_pool;
+        self
+    }
 
-            let val = match std::ptr::NonNull::new(ptr as *mut RcuLfhtNode<K, V>) {
-                Some(p) => p,
-                None => std::alloc::handle_alloc_error(layout),
-            };
+    /// Allocate nodes of objects with `allocator`, rather than with the Rust global allocator.
+    ///
+    /// Nodes free'd after a grace period (by urcu lib's call_rcu thread) are given back to the
+    /// same allocator. Clones of the hashtable share it.
+    pub fn allocator<A>(mut self, allocator: A) -> Self
+    where
+        A: std::alloc::GlobalAlloc + Send + Sync + 'static,
+    {
+        self.allocator = Some(NodeAllocator(std::sync::Arc::new(allocator)));
+        self
+    }
 
-            // initialize all 4 fields of this new struct
-            (*val.as_ptr()).node = std::mem::MaybeUninit::zeroed().assume_init();
-            (*val.as_ptr()).head = std::mem::MaybeUninit::zeroed().assume_init();
+    /// Use `hash_builder` to hash keys.
+    pub fn hasher<S2>(self, hash_builder: S2) -> RcuHtBuilder<S2> {
+        RcuHtBuilder {
+            init_size: self.init_size,
+            min_nr_alloc_buckets: self.min_nr_alloc_buckets,
+            max_nr_buckets: self.max_nr_buckets,
+            autoresize: self.autoresize,
+            accounting: self.accounting,
+            writer_shards: self.writer_shards,
+            lock_free_writes: self.lock_free_writes,
+            reclaim_mode: self.reclaim_mode,
+            capacity: self.capacity,
+            node_pool: self.node_pool,
+            allocator: self.allocator,
+            hash_builder,
+        }
+    }
 
-            let val = &mut *val.as_ptr();
+    /// Allocate the hashtable. Parameters are checked as in [`RcuHt::new`].
+    pub fn build<K, V>(self) -> Result<RcuHt<K, V, S>, RcuError>
+    where
+        K: Hash + Eq,
+        S: BuildHasher,
+    {
+        if self.writer_shards == 0 {
+            return Err(RcuError::InvalidParameters);
+        }
 
-            std::ptr::write(&mut val.key, key);
-            std::ptr::write(&mut val.data, value);
+        if self.capacity == Some(0) {
+            return Err(RcuError::InvalidSize("capacity must not be 0"));
+        }
 
-            // now add or replace it
-            urcu_read_lock();
+        if self.capacity.is_some() && self.writer_shards > 1 {
+            return Err(RcuError::InvalidSize(
+                "capacity cannot be used with several writer shards",
+            ));
+        }
 
-            // Return the node replaced upon success. If no node matching the key
-            // was present, return NULL, which also means the operation succeeded.
-            // This replacement operation should never fail.
-            // Call with rcu_read_lock held.
-            let old_node: *mut urcu_sys::cds_lfht_node = urcu_sys::cds_lfht_add_replace(
-                self.urcuht,
-                h,
-                Some(urcu_match_fn::<K, V>),
-                // coercion allowed from &T to *const T
-                // see : https://doc.rust-lang.org/reference/type-coercions.html#coercion-types */
-                &val.key as *const K as *const std::ffi::c_void,
-                &mut val.node as *mut urcu_sys::cds_lfht_node,
-            );
+        if self.capacity.is_some() && self.lock_free_writes {
+            return Err(RcuError::InvalidSize(
+                "capacity cannot be used with lock free writes",
+            ));
+        }
 
-            urcu_read_unlock();
+        let mut ht = RcuHt::create(
+            self.init_size,
+            self.min_nr_alloc_buckets,
+            self.max_nr_buckets,
+            self.autoresize,
+            self.accounting,
+            self.hash_builder,
+        )?;
 
-            // if add_replace returns an node, we must free it
-            if !old_node.is_null() {
-                // After successful replacement, a grace period must be waited for before
-                // freeing or re-using the memory reserved for the returned node.
-                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(old_node);
+        ht.shards = (0..self.writer_shards)
+            .map(|_| Mutex::new(RcuHtWriterGuard::new()))
+            .collect();
+        ht.lock_free_writes = self.lock_free_writes;
+        ht.reclaim_mode = self.reclaim_mode;
+        ht.capacity = self.capacity;
+        ht.pool = urcu_node_pool::<K, V>(self.node_pool, self.allocator);
 
-                // ask to free data after grace period
-                urcu_sys::call_rcu(&mut (*node).head, Some(urcu_free_node::<K, V>));
-            }
-        }
+        Ok(ht)
     }
+}
 
-    /// Delete the value indexed by the `key` from the hashtable.
+impl<K, V, S> Clone for RcuHt<K, V, S>
+where
+    K: Clone + Hash + Eq,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Allocate a new hashtable with the same parameters, and copy all objects in it.
     ///
-    /// This function may fail if node is not found.
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Result<(), RcuError>
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
-    {
-        let mut found = false;
-        let mut err = 0;
-
-        unsafe {
-            // RCU read-side lock must be held between lookup and removal.
-            urcu_read_lock();
+    /// Objects are copied under a single read lock: with concurrent writers, the copy is not an
+    /// atomic snapshot (some changes done meanwhile may be seen, others not).
+    fn clone(&self) -> Self {
+        let mut ht = RcuHt::create(
+            self.init_size,
+            self.min_nr_alloc_buckets,
+            self.max_nr_buckets,
+            self.autoresize,
+            self.accounting,
+            self.hash_builder.clone(),
+        )
+        .expect("Cannot create hashtable with parameters of an existing one");
 
-            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, key);
+        ht.shards = (0..self.shards.len())
+            .map(|_| Mutex::new(RcuHtWriterGuard::new()))
+            .collect();
+        ht.lock_free_writes = self.lock_free_writes;
+        ht.reclaim_mode = self.reclaim_mode;
+        ht.capacity = self.capacity;
+        ht.pool = urcu_node_pool::<K, V>(
+            self.node_pool_capacity(),
+            self.pool.as_ref().and_then(|pool| pool.allocator.clone()),
+        );
 
-            if !found_node.is_null() {
-                found = true;
-                // Return 0 if the node is successfully removed, negative value otherwise.
-                // Deleting a NULL node or an already removed node will fail with a negative value.
-                // Node can be looked up with cds_lfht_lookup and cds_lfht_next,
-                // followed by use of cds_lfht_iter_get_node.
+        {
+            let thread = self.thread();
+            let _rdlock = thread.rdlock();
 
-                // Call with rcu_read_lock held.
-                // Threads calling this API need to be registered RCU read-side threads.
-                err = urcu_sys::cds_lfht_del(self.urcuht, found_node);
+            // the new hashtable is not shared yet: there is no concurrent writer
+            let mut iter = RcuHtIter::<K, V>::new(self.urcuht);
+            loop {
+                let found_node = iter.next_node();
+                if found_node.is_null() {
+                    break;
+                }
 
-                // Ask to free data after grace period
-                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
-                urcu_sys::call_rcu(&mut (*node).head, Some(urcu_free_node::<K, V>));
+                unsafe {
+                    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                    let h = ht.key_hash(&(*node).key);
+                    let val = ht.alloc_node((*node).key.clone(), (*node).data.clone());
+                    // a bounded copy evicts (or expires) objects in the same order
+                    (*val).seq = (*node).seq;
+                    #[cfg(feature = "ttl")]
+                    {
+                        (*val).inserted = (*node).inserted;
+                    }
+                    // duplicate keys (see RcuHtWriter::insert_duplicate) are all copied
+                    urcu_add_node(ht.urcuht, h, val);
+                }
             }
 
-            urcu_read_unlock();
+            ht.insert_seq
+                .store(self.insert_seq.load(Ordering::Relaxed), Ordering::Relaxed);
         }
 
-        if found {
-            if err != 0 {
-                Err(RcuError::DeleteError(err))
-            } else {
-                Ok(())
-            }
-        } else {
-            Err(RcuError::NotFound)
-        }
+        ht
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::RcuHt;
+impl<K, V, S> IntoIterator for RcuHt<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = (K, V);
+    type IntoIter = std::vec::IntoIter<(K, V)>;
 
-    #[test]
-    fn it_works() {
-        // Type inference lets us omit an explicit type signature (which
-        // would be `HashMap<String, String>` in this example).
-        let ht = RcuHt::<String, String>::new(64, 64, 64, false)
-            .expect("Cannot create hashtable, probably due to invalid parameters");
-        let ht = std::sync::Arc::new(ht);
-        // Create a new thread to get book reviews.
-        let child = {
-            let ht = ht.clone();
-            std::thread::spawn(move || {
-                {
-                    // Get a read handle for this thread
-                    let ht = ht.thread();
-                    // wait until main thread adds some reviews
-                    std::thread::sleep(std::time::Duration::from_millis(100));
+    /// Consume the hashtable, returning all its objects.
+    ///
+    /// No other thread can access this hashtable anymore, so a poisoned write mutex is ignored.
+    /// As when it is dropped, removed objects are not reported to [`RcuHt::on_change`].
+    fn into_iter(self) -> Self::IntoIter {
+        let pairs = {
+            let thread = self.thread();
+            let guards = self
+                .shards
+                .iter()
+                .map(|mutex| match mutex.lock() {
+                    Ok(guard) => guard,
+                    Err(err) => err.into_inner(),
+                })
+                .collect();
+            let _writer = RcuHtWriter::new(self.urcuht, &thread, None, guards);
+            unsafe { urcu_drain::<K, V>(self.urcuht) }
+        };
 
-                    let rdlock = ht.rdlock();
+        pairs.into_iter()
+    }
+}
 
-                    let review = rdlock.get("Adventures of Huckleberry Finn");
-                    assert_eq!(review.is_some(), true);
-                    let review = review.unwrap();
-                    assert_eq!(review.eq("My favorite book."), true);
-                }
+impl<K, V, S> Drop for RcuHt<K, V, S> {
+    /// Release an instance of a RCU hashtable.
+    ///
+    /// All remaining objects are removed and dropped first: urcu lib requires an empty hashtable.
+    fn drop(&mut self) {
+        // urcu lib calls below need a registered thread
+        let counted = urcu_thread_enter();
 
-                // uncomment this to check rdlock cannot live longer than ht thread
-                /*
-                let _out_error = {
-                    // Get a read handle for this thread
-                    let ht = ht.thread();
+        unsafe {
+            // must be called when there is no more writer or reader able to access this hashtable.
+            drop(urcu_drain::<K, V>(self.urcuht));
 
-                    // get a rdlock and return it
-                    ht.rdlock()
-                };
-                */
+            urcu_sys::cds_lfht_destroy(self.urcuht, std::ptr::null_mut());
+        }
 
-                // uncommend this to check wrlock cannot live longer than ht thread
-                /*
-                let _out_error = {
-                    // Get a read handle for this thread
-                    let ht = ht.thread();
+        // nodes being free'd by urcu lib's call_rcu thread are given back to the node pool (or
+        // its allocator), and counted in pending reclaims: wait for them before both are dropped.
+        if self.pool.is_some() || self.pending_reclaims.load(Ordering::Acquire) > 0 {
+            flavor::barrier();
+        }
 
-                    // get a rdlock and return it
-                    ht.wrlock()
-                };
-                */
+        urcu_thread_leave(counted);
+    }
+}
 
-                // uncomment this to check a ref to a value cannot live longer than rdlock
-                /*
-                // Get a read handle for this thread
-                let ht = ht.thread();
+/// Allocator of nodes, see [`RcuHtBuilder::allocator`].
+#[derive(Clone)]
+struct NodeAllocator(std::sync::Arc<dyn std::alloc::GlobalAlloc + Send + Sync>);
 
-                let _out_error = {
-                    // get a rdlock
-                    let rdlock = ht.rdlock();
+impl std::fmt::Debug for NodeAllocator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("NodeAllocator")
+    }
+}
 
-                    // get a reference to a value then try to return it will lock is released
-                    rdlock.get("Adventures of Huckleberry Finn")
-                };
-                */
-            })
-        };
+/// Memory of free'd nodes kept for reuse, see [`RcuHtBuilder::node_pool`].
+///
+/// All nodes of a hashtable have the same layout. Nodes are given back from any thread,
+/// including urcu lib's call_rcu thread.
+struct NodePool {
+    free: Mutex<Vec<*mut u8>>,
+    capacity: usize,
+    layout: std::alloc::Layout,
+    /// allocator of nodes, Rust global allocator if None
+    allocator: Option<NodeAllocator>,
+}
 
-        let ht = ht.thread();
-        {
-            let mut wrlock = ht.wrlock().unwrap();
-            wrlock.insert_or_replace(
-                "Adventures of Huckleberry Finn".to_string(),
-                "My favorite book.".to_string(),
-            );
+impl NodePool {
+    fn new(capacity: usize, layout: std::alloc::Layout, allocator: Option<NodeAllocator>) -> Self {
+        NodePool {
+            free: Mutex::new(Vec::with_capacity(capacity)),
+            capacity,
+            layout,
+            allocator,
         }
+    }
 
-        {
-            let mut wrlock = ht.wrlock().unwrap();
-            wrlock.insert_or_replace(
-                "Grimms' Fairy Tales".to_string(),
-                "Masterpiece.".to_string(),
-            );
+    /// Allocate memory of a new node, null on failure.
+    unsafe fn alloc(&self) -> *mut u8 {
+        match &self.allocator {
+            Some(allocator) => allocator.0.alloc(self.layout),
+            None => std::alloc::alloc(self.layout),
         }
+    }
 
-        child.join().expect("cannot join thread");
+    /// Give back memory of a node to the allocator.
+    unsafe fn dealloc(&self, ptr: *mut u8) {
+        match &self.allocator {
+            Some(allocator) => allocator.0.dealloc(ptr, self.layout),
+            None => std::alloc::dealloc(ptr, self.layout),
+        }
+    }
+
+    /// Get memory of a free'd node, null if there is none.
+    fn get(&self) -> *mut u8 {
+        let mut free = self.free.lock().unwrap_or_else(|err| err.into_inner());
+        free.pop().unwrap_or(std::ptr::null_mut())
+    }
+
+    /// Keep memory of a free'd node for reuse. Returns false if the pool is full.
+    fn put(&self, ptr: *mut u8) -> bool {
+        let mut free = self.free.lock().unwrap_or_else(|err| err.into_inner());
+        if free.len() >= self.capacity {
+            return false;
+        }
+
+        free.push(ptr);
+        true
+    }
+
+    /// Number of nodes currently kept for reuse.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.free
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len()
+    }
+}
+
+impl Drop for NodePool {
+    fn drop(&mut self) {
+        let free = self.free.get_mut().unwrap_or_else(|err| err.into_inner());
+        for ptr in std::mem::take(free) {
+            unsafe {
+                self.dealloc(ptr);
+            }
+        }
+    }
+}
+
+/// helper function to create the node pool of a hashtable, None if `capacity` is 0 and there is
+/// no custom allocator.
+fn urcu_node_pool<K, V>(
+    capacity: usize,
+    allocator: Option<NodeAllocator>,
+) -> Option<Box<NodePool>> {
+    (capacity > 0 || allocator.is_some()).then(|| {
+        Box::new(NodePool::new(
+            capacity,
+            std::alloc::Layout::new::<RcuLfhtNode<K, V>>(),
+            allocator,
+        ))
+    })
+}
+
+/// This describes every object stored in hashtable.
+#[repr(C)]
+struct RcuLfhtNode<K, V> {
+    /// internal node to link to other objects it in hashtable
+    node: urcu_sys::cds_lfht_node,
+    /// data structure used for delayed free
+    head: urcu_sys::rcu_head,
+    /// insertion sequence number, used to evict oldest objects of bounded hashtables
+    seq: u64,
+    /// node pool (and allocator) this node memory is given back to when free'd, null if none
+    pool: *const NodePool,
+    /// pending reclaims counter of the hashtable, decremented when this node is free'd after a
+    /// grace period, null if none
+    pending_reclaims: *const AtomicUsize,
+    /// insertion time, used to remove expired objects
+    #[cfg(feature = "ttl")]
+    inserted: std::time::Instant,
+    /// object key (user data)
+    key: K,
+    /// object data (user data)
+    data: V,
+}
+
+/// Match function callback used when looking for objects.
+/// Returns 1 if current node key and lookup key are equals, 0 otherwise.
+/// Called by urcu_sys::cds_lfht_lookup
+/// Unsized callback version
+///
+/// There is no need to cache the key hash in RcuLfhtNode: urcu lib stores it in its own node
+/// (reverse_hash), and only calls this callback for nodes whose hash equals the lookup hash.
+/// Expensive key comparisons are thus mostly done for the matching key only.
+unsafe extern "C" fn urcu_match_ref_fn<Q, K, V>(
+    node: *mut urcu_sys::cds_lfht_node,
+    key: *const std::ffi::c_void,
+) -> i32
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Eq,
+{
+    let key = *(key as *const &Q);
+    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(node);
+    let node_key = (*node).key.borrow();
+
+    if key.eq(node_key) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Match function callback used when looking for objects.
+/// Returns 1 if current node key and lookup key are equals, 0 otherwise.
+/// Called by urcu_sys::cds_lfht_lookup
+/// Sized callback version
+unsafe extern "C" fn urcu_match_fn<K, V>(
+    node: *mut urcu_sys::cds_lfht_node,
+    key: *const std::ffi::c_void,
+) -> i32
+where
+    K: Eq,
+{
+    let key = key as *const K;
+    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(node);
+
+    if (*key).eq(&(*node).key) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Get back to RcuLfhtNode rust type from a "node" pointer. C-like dark pointer operations and casting.
+unsafe fn urcu_cds_lfht_node_to_rust_type<K, V>(
+    node: *mut urcu_sys::cds_lfht_node,
+) -> *mut RcuLfhtNode<K, V> {
+    let offset = memoffset::offset_of!(RcuLfhtNode::<K, V>, node);
+
+    let ptr = node.cast::<u8>();
+    ptr.sub(offset).cast::<RcuLfhtNode<K, V>>()
+}
+
+/// Get back to RcuLfhtNode rust type from a "head" pointer. C-like dark pointer operations and casting.
+unsafe fn urcu_cds_lfht_head_to_rust_type<K, V>(
+    node: *mut urcu_sys::rcu_head,
+) -> *mut RcuLfhtNode<K, V> {
+    let offset = memoffset::offset_of!(RcuLfhtNode::<K, V>, head);
+
+    let ptr = node.cast::<u8>();
+    ptr.sub(offset).cast::<RcuLfhtNode<K, V>>()
+}
+
+/// Helper function used to perform lookup (used at multiple places).
+/// This function must be called with rcu_read_lock held.
+/// Threads calling this API need to be registered (flavor::register_thread).
+unsafe fn urcu_get_node<Q, K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    hash: u64,
+    key: &Q,
+) -> *mut urcu_sys::cds_lfht_node
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Eq,
+{
+    let mut iter = urcu_lookup::<Q, K, V>(ht, hash, key);
+
+    let found_node: *mut urcu_sys::cds_lfht_node = urcu_sys::cds_lfht_iter_get_node(&mut iter);
+
+    found_node
+}
+
+/// Helper function used to perform lookup, returning an iterator positioned on the found node.
+/// This function must be called with rcu_read_lock held.
+unsafe fn urcu_lookup<Q, K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    hash: u64,
+    key: &Q,
+) -> urcu_sys::cds_lfht_iter
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Eq,
+{
+    let mut iter: urcu_sys::cds_lfht_iter = std::mem::MaybeUninit::zeroed().assume_init();
+
+    // cds_lfht_lookup - lookup a node by key.
+    // @ht: the hash table.
+    // @hash: the key hash.
+    // @match: the key match function.
+    // @key: the current node key.
+    // @iter: node, if found (output). *iter->node set to NULL if not found.
+    // This function acts as a rcu_dereference() to read the node pointer.
+    urcu_sys::cds_lfht_lookup(
+        ht,
+        hash,
+        Some(urcu_match_ref_fn::<Q, K, V>),
+        &key as *const &Q as *const std::ffi::c_void,
+        &mut iter as *mut urcu_sys::cds_lfht_iter,
+    );
+
+    iter
+}
+
+/// Helper function used to move a lookup iterator to the next node with the same key.
+/// *iter->node is set to NULL if there is no such node.
+/// This function must be called with rcu_read_lock held.
+unsafe fn urcu_next_duplicate<Q, K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    key: &Q,
+    iter: &mut urcu_sys::cds_lfht_iter,
+) where
+    K: Borrow<Q>,
+    Q: ?Sized + Eq,
+{
+    // cds_lfht_next_duplicate - get the next item with same key, after iterator.
+    // *iter->node set to NULL if no following node exists with same key.
+    urcu_sys::cds_lfht_next_duplicate(
+        ht,
+        Some(urcu_match_ref_fn::<Q, K, V>),
+        &key as *const &Q as *const std::ffi::c_void,
+        iter as *mut urcu_sys::cds_lfht_iter,
+    );
+}
+
+/// Check that a new hashtable is usable: it must be empty, both when walked and counted.
+/// It gives a clear error instead of a later crash if urcu lib is not initialized properly.
+unsafe fn urcu_sanity_check(ht: *mut urcu_sys::cds_lfht) -> bool {
+    // urcu lib calls below need a registered thread
+    let counted = urcu_thread_enter();
+    flavor::read_lock();
+
+    let mut iter: urcu_sys::cds_lfht_iter = std::mem::MaybeUninit::zeroed().assume_init();
+    urcu_sys::cds_lfht_first(ht, &mut iter as *mut urcu_sys::cds_lfht_iter);
+    let empty = urcu_sys::cds_lfht_iter_get_node(&mut iter).is_null() && urcu_count_nodes(ht) == 0;
+
+    flavor::read_unlock();
+    urcu_thread_leave(counted);
+
+    empty
+}
+
+/// Flag of cds_lfht_new enabling split counters (not exported by urcu-sys, see rculfhash.h).
+const CDS_LFHT_ACCOUNTING: i32 = 1 << 1;
+
+/// Helper function to count objects in hashtable.
+/// This function must be called with rcu_read_lock held.
+/// The count is exact if there is no concurrent writer, approximate otherwise.
+unsafe fn urcu_count_nodes(ht: *mut urcu_sys::cds_lfht) -> u64 {
+    urcu_count_nodes_split(ht).1
+}
+
+/// Helper function to count objects in hashtable, with the approximate counts given by split
+/// counters before and after the walk (0 if hashtable has no split counters).
+/// This function must be called with rcu_read_lock held.
+unsafe fn urcu_count_nodes_split(ht: *mut urcu_sys::cds_lfht) -> (i64, u64, i64) {
+    let mut split_count_before: std::os::raw::c_long = 0;
+    let mut count: std::os::raw::c_ulong = 0;
+    let mut split_count_after: std::os::raw::c_long = 0;
+
+    urcu_sys::cds_lfht_count_nodes(
+        ht,
+        &mut split_count_before,
+        &mut count,
+        &mut split_count_after,
+    );
+
+    (
+        split_count_before as i64,
+        count as u64,
+        split_count_after as i64,
+    )
+}
+
+/// Callback function, called after some delay, when it is time to free a node.
+unsafe extern "C" fn urcu_free_node<K, V>(head: *mut urcu_sys::rcu_head) {
+    let node = urcu_cds_lfht_head_to_rust_type::<K, V>(head);
+    urcu_free_reclaimed_node(node);
+}
+
+/// Callback function of defer_rcu, called after some delay, when it is time to free a node.
+unsafe extern "C" fn urcu_defer_free_node<K, V>(p: *mut std::os::raw::c_void) {
+    urcu_free_reclaimed_node(p as *mut RcuLfhtNode<K, V>);
+}
+
+/// Free a node after a grace period, from a call_rcu or defer_rcu callback.
+/// It aborts if a destructor panics: unwinding must not cross urcu lib threads.
+unsafe fn urcu_free_reclaimed_node<K, V>(node: *mut RcuLfhtNode<K, V>) {
+    urcu_metrics_record("deferred_free");
+
+    let pending_reclaims = (*node).pending_reclaims;
+
+    let destroy = std::panic::AssertUnwindSafe(|| urcu_destroy_node(node));
+    if std::panic::catch_unwind(destroy).is_err() {
+        std::process::abort();
+    }
+
+    // last access to the hashtable: it may be dropped as soon as the counter reaches 0
+    if !pending_reclaims.is_null() {
+        (*pending_reclaims).fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Drop key and data of a node, and free it. No reader must be able to access it anymore.
+unsafe fn urcu_destroy_node<K, V>(node: *mut RcuLfhtNode<K, V>) {
+    std::ptr::drop_in_place(&mut (*node).key);
+    std::ptr::drop_in_place(&mut (*node).data);
+
+    urcu_dealloc_node(node);
+}
+
+/// Give back memory of a node, whose key and data are already dropped or moved out.
+/// Memory is kept in the node pool while it is not full, else given back to its allocator.
+unsafe fn urcu_dealloc_node<K, V>(node: *mut RcuLfhtNode<K, V>) {
+    let pool = (*node).pool;
+    if !pool.is_null() {
+        if !(*pool).put(node as *mut u8) {
+            (*pool).dealloc(node as *mut u8);
+        }
+        return;
+    }
+
+    let layout = std::alloc::Layout::new::<RcuLfhtNode<K, V>>();
+    std::alloc::dealloc(node as *mut u8, layout);
+}
+
+/// Removed (or replaced) nodes, free'd according to `mode` when dropped: they are not leaked on
+/// unwind from a user callback. It must be dropped outside of a read-side critical section, so
+/// it must be created before the read lock.
+struct UrcuReclaimGuard<K, V> {
+    nodes: Vec<*mut RcuLfhtNode<K, V>>,
+    mode: ReclaimMode,
+}
+
+impl<K, V> UrcuReclaimGuard<K, V> {
+    fn new(mode: ReclaimMode) -> Self {
+        UrcuReclaimGuard {
+            nodes: Vec::new(),
+            mode,
+        }
+    }
+}
+
+impl<K, V> Drop for UrcuReclaimGuard<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            urcu_reclaim_nodes(&self.nodes, self.mode);
+        }
+    }
+}
+
+/// Free removed (or replaced) nodes, according to `mode`.
+/// Must be called outside of a read-side critical section in synchronous and defer_rcu modes.
+unsafe fn urcu_reclaim_nodes<K, V>(nodes: &[*mut RcuLfhtNode<K, V>], mode: ReclaimMode) {
+    // a thread whose defer_rcu queue cannot be allocated uses call_rcu instead
+    let mode = match mode {
+        ReclaimMode::DeferRcu if !nodes.is_empty() && !urcu_defer_thread_enter() => {
+            ReclaimMode::Deferred
+        }
+        mode => mode,
+    };
+
+    match mode {
+        ReclaimMode::Deferred | ReclaimMode::DeferRcu => {
+            for &node in nodes {
+                let pending_reclaims = (*node).pending_reclaims;
+                if !pending_reclaims.is_null() {
+                    (*pending_reclaims).fetch_add(1, Ordering::Relaxed);
+                }
+
+                // ask to free data after grace period
+                match mode {
+                    ReclaimMode::DeferRcu => {
+                        flavor::defer_rcu(urcu_defer_free_node::<K, V>, node as *mut _)
+                    }
+                    _ => flavor::call_rcu(&mut (*node).head, urcu_free_node::<K, V>),
+                }
+            }
+        }
+        ReclaimMode::Synchronous => {
+            if nodes.is_empty() {
+                return;
+            }
+
+            // Wait for all readers which could still access removed nodes
+            flavor::synchronize();
+
+            for &node in nodes {
+                urcu_destroy_node(node);
+            }
+        }
+    }
+}
+
+/// Allocate a new RcuLfhtNode to store key and data, reusing memory of `pool` (if not null) or
+/// allocating it from the allocator of `pool`.
+/// Give back key and data on allocation failure.
+unsafe fn urcu_try_alloc_node<K, V>(
+    key: K,
+    data: V,
+    pool: *const NodePool,
+) -> Result<*mut RcuLfhtNode<K, V>, (K, V)> {
+    let layout = std::alloc::Layout::new::<RcuLfhtNode<K, V>>();
+
+    let ptr = match pool.is_null() {
+        /* alloc style from https://doc.rust-lang.org/nomicon/vec/vec-alloc.html */
+        true => std::alloc::alloc(layout),
+        false => match (*pool).get() {
+            ptr if ptr.is_null() => (*pool).alloc(),
+            ptr => ptr,
+        },
+    };
+
+    let val = match std::ptr::NonNull::new(ptr as *mut RcuLfhtNode<K, V>) {
+        Some(p) => p,
+        None => return Err((key, data)),
+    };
+
+    // initialize all fields of this new struct.
+    // urcu structs are initialized field by field, rather than assuming all-zero is a valid value
+    // (cds_lfht_node_init is a static inline no-op, which is not exported by urcu lib).
+    std::ptr::write(
+        &mut (*val.as_ptr()).node,
+        urcu_sys::cds_lfht_node {
+            next: std::ptr::null_mut(),
+            reverse_hash: 0,
+        },
+    );
+    std::ptr::write(
+        &mut (*val.as_ptr()).head,
+        urcu_sys::rcu_head {
+            next: urcu_sys::cds_wfcq_node {
+                next: std::ptr::null_mut(),
+            },
+            func: None,
+        },
+    );
+    (*val.as_ptr()).seq = 0;
+    (*val.as_ptr()).pool = pool;
+    (*val.as_ptr()).pending_reclaims = std::ptr::null();
+    #[cfg(feature = "ttl")]
+    std::ptr::write(&mut (*val.as_ptr()).inserted, std::time::Instant::now());
+
+    let val = &mut *val.as_ptr();
+
+    std::ptr::write(&mut val.key, key);
+    std::ptr::write(&mut val.data, data);
+
+    Ok(val)
+}
+
+/// Add an allocated node in hashtable, replacing any existing node with the same key.
+/// The replaced node is free'd after a grace period, according to `mode`.
+unsafe fn urcu_replace_node<K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    h: u64,
+    val: *mut RcuLfhtNode<K, V>,
+    mode: ReclaimMode,
+) where
+    K: Eq,
+{
+    let node = urcu_add_replace_node(ht, h, val);
+
+    // if add_replace returns an node, we must free it
+    if !node.is_null() {
+        urcu_reclaim_nodes(&[node], mode);
+    }
+}
+
+/// Add an allocated node in hashtable, returning the replaced node (or null).
+///
+/// After successful replacement, a grace period must be waited for before
+/// freeing or re-using the memory reserved for the returned node.
+/// A replaced node is returned to a single caller, even with concurrent writers.
+unsafe fn urcu_add_replace_node<K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    h: u64,
+    val: *mut RcuLfhtNode<K, V>,
+) -> *mut RcuLfhtNode<K, V>
+where
+    K: Eq,
+{
+    let val = &mut *val;
+
+    // now add or replace it
+    flavor::read_lock();
+
+    // Return the node replaced upon success. If no node matching the key
+    // was present, return NULL, which also means the operation succeeded.
+    // This replacement operation should never fail.
+    // Call with rcu_read_lock held.
+    let old_node: *mut urcu_sys::cds_lfht_node = urcu_sys::cds_lfht_add_replace(
+        ht,
+        h,
+        Some(urcu_match_fn::<K, V>),
+        // coercion allowed from &T to *const T
+        // see : https://doc.rust-lang.org/reference/type-coercions.html#coercion-types */
+        &val.key as *const K as *const std::ffi::c_void,
+        &mut val.node as *mut urcu_sys::cds_lfht_node,
+    );
+
+    flavor::read_unlock();
+
+    if old_node.is_null() {
+        std::ptr::null_mut()
+    } else {
+        urcu_cds_lfht_node_to_rust_type::<K, V>(old_node)
+    }
+}
+
+/// Replace the node an iterator is positioned on with an allocated node of the same key,
+/// returning the replaced node (or null if it was removed meanwhile).
+///
+/// Unlike urcu_add_replace_node, the node is not looked up again by key: with duplicate keys,
+/// the first node of the key would be replaced instead of this one.
+/// On success, caller must reclaim the returned node once out of the read-side critical section.
+/// Call with rcu_read_lock held.
+unsafe fn urcu_replace_at<K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    old_iter: &mut urcu_sys::cds_lfht_iter,
+    val: *mut RcuLfhtNode<K, V>,
+) -> *mut RcuLfhtNode<K, V>
+where
+    K: Eq,
+{
+    let old_node = old_iter.node;
+    let h = (*old_node).reverse_hash.reverse_bits();
+
+    // cds_lfht_replace - replace the node pointed to by "old_iter" with "new_node".
+    // Returns 0 on success, -ENOENT if the old node is already removed (or replaced).
+    let ret = urcu_sys::cds_lfht_replace(
+        ht,
+        old_iter as *mut urcu_sys::cds_lfht_iter,
+        h,
+        Some(urcu_match_fn::<K, V>),
+        &(*val).key as *const K as *const std::ffi::c_void,
+        &mut (*val).node as *mut urcu_sys::cds_lfht_node,
+    );
+
+    if ret == 0 {
+        urcu_cds_lfht_node_to_rust_type::<K, V>(old_node)
+    } else {
+        std::ptr::null_mut()
+    }
+}
+
+/// Add an allocated node, even if its key is already in hashtable (duplicate keys).
+unsafe fn urcu_add_node<K, V>(ht: *mut urcu_sys::cds_lfht, h: u64, val: *mut RcuLfhtNode<K, V>) {
+    flavor::read_lock();
+
+    // cds_lfht_add - add a node to the hash table.
+    // This function supports adding redundant keys into the table.
+    // Call with rcu_read_lock held.
+    urcu_sys::cds_lfht_add(ht, h, &mut (*val).node as *mut urcu_sys::cds_lfht_node);
+
+    flavor::read_unlock();
+}
+
+/// Add an allocated node, only if its key is not already in hashtable.
+/// On conflict, the node is free'd and its value is given back.
+unsafe fn urcu_insert_unique<K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    h: u64,
+    val: *mut RcuLfhtNode<K, V>,
+) -> Result<(), V>
+where
+    K: Eq,
+{
+    flavor::read_lock();
+
+    // Return the node added upon success, or the existing node matching the key.
+    // Call with rcu_read_lock held.
+    let found_node: *mut urcu_sys::cds_lfht_node = urcu_sys::cds_lfht_add_unique(
+        ht,
+        h,
+        Some(urcu_match_fn::<K, V>),
+        &(*val).key as *const K as *const std::ffi::c_void,
+        &mut (*val).node as *mut urcu_sys::cds_lfht_node,
+    );
+
+    flavor::read_unlock();
+
+    if std::ptr::eq(found_node, &(*val).node) {
+        Ok(())
+    } else {
+        // Our node was never published so no reader can see it:
+        // give back the value without waiting for a grace period.
+        let (_key, value) = urcu_take_node(val);
+        Err(value)
+    }
+}
+
+/// Delete the oldest nodes (lowest insertion sequence number) until there are no more than
+/// `capacity` nodes. Deleted nodes are free'd after a grace period, according to `mode`.
+/// `evicted_key` is called with their key before. Must be called outside of a read-side critical
+/// section.
+unsafe fn urcu_evict_oldest<K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    capacity: usize,
+    mode: ReclaimMode,
+    mut evicted_key: impl FnMut(&K),
+) {
+    loop {
+        let mut count = 0;
+        let mut oldest: *mut RcuLfhtNode<K, V> = std::ptr::null_mut();
+        let mut evicted = None;
+
+        // RCU read-side lock must be held while walking the hashtable, and until removal.
+        flavor::read_lock();
+
+        let mut iter = RcuHtIter::<K, V>::new(ht);
+        loop {
+            let found_node = iter.next_node();
+            if found_node.is_null() {
+                break;
+            }
+
+            count += 1;
+            let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+            if oldest.is_null() || (*node).seq < (*oldest).seq {
+                oldest = node;
+            }
+        }
+
+        // It may fail if a concurrent writer removed it meanwhile: just walk again.
+        if count > capacity && urcu_delete_node(ht, &mut (*oldest).node) == 0 {
+            evicted = Some(oldest);
+        }
+
+        flavor::read_unlock();
+
+        if let Some(node) = evicted {
+            evicted_key(&(*node).key);
+            urcu_reclaim_nodes(&[node], mode);
+            count -= 1;
+        }
+
+        if count <= capacity {
+            break;
+        }
+    }
+}
+
+/// Delete the value indexed by the `key` from the hashtable, and all its duplicates if
+/// `duplicates` is true.
+/// It fails with DeleteError if a node is removed by someone else between lookup and removal:
+/// other deleted nodes are still free'd. Deleted nodes are free'd after a single grace period,
+/// according to `mode`. `removed` is called once with their key before.
+unsafe fn urcu_remove<Q, K, V>(
+    ht: *mut urcu_sys::cds_lfht,
+    h: u64,
+    key: &Q,
+    duplicates: bool,
+    mode: ReclaimMode,
+    removed: impl FnOnce(&K),
+) -> Result<(), RcuError>
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Eq,
+{
+    let mut found = false;
+    let mut err = 0;
+    // only allocated if there are duplicates
+    let mut nodes = Vec::new();
+    let mut first: *mut RcuLfhtNode<K, V> = std::ptr::null_mut();
+
+    // RCU read-side lock must be held between lookup and removal.
+    flavor::read_lock();
+
+    let mut iter = urcu_lookup::<Q, K, V>(ht, h, key);
+    while !iter.node.is_null() {
+        found = true;
+
+        // only free it if it was us who removed it
+        match urcu_delete_node(ht, iter.node) {
+            0 if first.is_null() => first = urcu_cds_lfht_node_to_rust_type::<K, V>(iter.node),
+            0 => nodes.push(urcu_cds_lfht_node_to_rust_type::<K, V>(iter.node)),
+            code => err = code,
+        }
+
+        if !duplicates {
+            break;
+        }
+        urcu_next_duplicate::<Q, K, V>(ht, key, &mut iter);
+    }
+
+    flavor::read_unlock();
+
+    if !first.is_null() {
+        removed(&(*first).key);
+
+        if nodes.is_empty() {
+            urcu_reclaim_nodes(&[first], mode);
+        } else {
+            nodes.push(first);
+            urcu_reclaim_nodes(&nodes, mode);
+        }
+    }
+
+    if !found {
+        Err(RcuError::NotFound)
+    } else if err != 0 {
+        Err(RcuError::DeleteError(DeleteErrorKind::from_code(err)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Delete an already located node (no new lookup).
+/// Returns 0 on success, negative value otherwise. On success, caller must free the node
+/// (urcu_reclaim_nodes) once out of the read-side critical section.
+/// Call with rcu_read_lock held.
+unsafe fn urcu_delete_node(
+    ht: *mut urcu_sys::cds_lfht,
+    found_node: *mut urcu_sys::cds_lfht_node,
+) -> i32 {
+    // Return 0 if the node is successfully removed, negative value otherwise.
+    // Deleting a NULL node or an already removed node will fail with a negative value.
+    // Node can be looked up with cds_lfht_lookup and cds_lfht_next,
+    // followed by use of cds_lfht_iter_get_node.
+
+    // Call with rcu_read_lock held.
+    // Threads calling this API need to be registered RCU read-side threads.
+    urcu_sys::cds_lfht_del(ht, found_node)
+}
+
+/// Delete all nodes from hashtable, then move their keys and data out once a grace period has elapsed.
+/// Must be called under write lock, outside of a read-side critical section.
+unsafe fn urcu_drain<K, V>(ht: *mut urcu_sys::cds_lfht) -> Vec<(K, V)> {
+    let mut nodes = Vec::new();
+
+    // RCU read-side lock must be held while walking the hashtable.
+    flavor::read_lock();
+
+    let mut iter = RcuHtIter::<K, V>::new(ht);
+    loop {
+        let found_node = iter.next_node();
+        if found_node.is_null() {
+            break;
+        }
+
+        // Only a single writer can delete nodes, so this should never fail.
+        if urcu_sys::cds_lfht_del(ht, found_node) == 0 {
+            nodes.push(urcu_cds_lfht_node_to_rust_type::<K, V>(found_node));
+        }
+    }
+
+    flavor::read_unlock();
+
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+
+    // Wait for all readers which could still access deleted nodes
+    flavor::synchronize();
+
+    nodes.into_iter().map(|node| urcu_take_node(node)).collect()
+}
+
+/// Move key and data out of a node which was deleted from hashtable, then free the node memory.
+/// A grace period must have elapsed since deletion: no reader can still access this node.
+unsafe fn urcu_take_node<K, V>(node: *mut RcuLfhtNode<K, V>) -> (K, V) {
+    let key = std::ptr::read(&(*node).key);
+    let data = std::ptr::read(&(*node).data);
+
+    urcu_dealloc_node(node);
+
+    (key, data)
+}
+
+// thread local flag for thread register / unregister
+// since this is a local thread storage, there is no concurrency, so no need for atomics
+thread_local! {
+    static URCU_THREAD_REGISTERED_COUNT: Cell<u32> = const { Cell::new(0) };
+    static URCU_THREAD_EXTERNAL_REGISTRATION: Cell<bool> = const { Cell::new(false) };
+    static URCU_THREAD_DEFER_REGISTERED: Cell<bool> = const { Cell::new(false) };
+    /// the thread is registered by RcuHtThread handles (it stays registered without handles with
+    /// `manual_thread_lifecycle` feature)
+    static URCU_THREAD_AUTO_REGISTRATION: UrcuThreadRegistration =
+        const { UrcuThreadRegistration(Cell::new(false)) };
+}
+
+/// Thread registration done by RcuHtThread handles.
+///
+/// A thread must not exit while registered in urcu lib (its reader state would stay in urcu lib
+/// registry): a thread still registered when it exits is unregistered by this destructor.
+struct UrcuThreadRegistration(Cell<bool>);
+
+impl UrcuThreadRegistration {
+    fn replace(&self, registered: bool) -> bool {
+        self.0.replace(registered)
+    }
+}
+
+impl Drop for UrcuThreadRegistration {
+    fn drop(&mut self) {
+        if self.0.replace(false) {
+            urcu_defer_thread_leave();
+            flavor::unregister_thread();
+        }
+    }
+}
+
+/// Register the current thread in urcu lib, and disable automatic registration done by
+/// [`RcuHtThread`] handles on this thread.
+///
+/// This is meant for programs which manage urcu thread lifecycle themselves (embedding this crate
+/// in a larger C/Rust program for instance). Handles created afterwards on this thread do not
+/// register or unregister it anymore, until [`unregister_current_thread`] is called.
+///
+/// A thread must never be registered twice in urcu lib: do not call this function if the thread
+/// is already registered by other code, and do not call it while [`RcuHtThread`] handles are alive
+/// on this thread (this panics). A thread left registered by handles (`manual_thread_lifecycle`
+/// feature) is not registered again.
+pub fn register_current_thread() {
+    if URCU_THREAD_EXTERNAL_REGISTRATION.with(|cell| cell.get()) {
+        return;
+    }
+
+    assert_eq!(
+        URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.get()),
+        0,
+        "current thread is already registered by RcuHtThread handles"
+    );
+
+    if !URCU_THREAD_AUTO_REGISTRATION.with(|registration| registration.replace(false)) {
+        flavor::register_thread();
+    }
+
+    URCU_THREAD_EXTERNAL_REGISTRATION.with(|cell| cell.set(true));
+}
+
+/// Unregister the current thread from urcu lib, and restore automatic registration done by
+/// [`RcuHtThread`] handles on this thread.
+///
+/// It must be called once all read and write locks held by this thread are released.
+/// It does nothing if the thread was not registered by [`register_current_thread`].
+///
+/// With `manual_thread_lifecycle` feature, it also unregisters a thread left registered by
+/// [`RcuHtThread`] handles, which must all be dropped (this panics otherwise). Such a thread is
+/// also unregistered when it exits.
+pub fn unregister_current_thread() {
+    if !URCU_THREAD_EXTERNAL_REGISTRATION.with(|cell| cell.get()) {
+        #[cfg(feature = "manual_thread_lifecycle")]
+        {
+            assert_eq!(
+                URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.get()),
+                0,
+                "current thread is still used by RcuHtThread handles"
+            );
+            urcu_thread_auto_unregister();
+        }
+        return;
+    }
+
+    urcu_defer_thread_leave();
+    flavor::unregister_thread();
+
+    URCU_THREAD_EXTERNAL_REGISTRATION.with(|cell| cell.set(false));
+}
+
+/// Per thread object used to provide safe access to RCU hashtable.
+///
+/// It registers the current thread if needed (the first reader or writer object triggers the registration).
+/// It unregisters the current thread when no more objects are alive in this thread.
+/// Nothing is done if the thread is registered with [`register_current_thread`].
+///
+/// It is bound to the thread which created it: it can be neither moved to another thread...
+///
+/// ```compile_fail
+/// let ht = urcu_ht::RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+/// let thread = ht.thread();
+/// std::thread::scope(|s| {
+///     s.spawn(move || drop(thread));
+/// });
+/// ```
+///
+/// ... nor shared with another thread.
+///
+/// ```compile_fail
+/// let ht = urcu_ht::RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+/// let thread = ht.thread();
+/// std::thread::scope(|s| {
+///     s.spawn(|| thread.rdlock().get(&1).is_some());
+/// });
+/// ```
+pub struct RcuHtThread<'ht, K, V, S = WyHashBuilder> {
+    ht: &'ht RcuHt<K, V, S>,
+    /// true if this handle is accounted in thread reference counter
+    counted: bool,
+    /// registration is per thread: the handle must be neither Send nor Sync
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'ht, K, V, S> Clone for RcuHtThread<'ht, K, V, S> {
+    /// Get another handle for the same thread, to pass it around within this thread.
+    ///
+    /// The thread is already registered by this handle: it stays registered until all clones are
+    /// dropped.
+    fn clone(&self) -> Self {
+        RcuHtThread {
+            ht: self.ht,
+            counted: urcu_thread_enter(),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'ht, K, V, S> RcuHtThread<'ht, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Get a new "read" handle.
+    /// A different handle is needed for each thread doing "read" operations.
+    /// It registers this thread in urcu lib.
+    /// It must stick to a single thread. One must not try to move this handle between threads.
+    pub fn new(ht: &'ht RcuHt<K, V, S>) -> Self {
+        let counted = urcu_thread_enter();
+
+        // Return an object with a reference to the hashtable (and so to its shared write mutex).
+        // The write mutex prevents concurrent write on this hashtable.
+        // Since hashtable is a reference, we are sure original hashtable cannot be deleted before this object.
+        // This is needed to protect hashtable deletion.
+        RcuHtThread {
+            ht,
+            counted,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Get a writer for the whole hashtable, locking all writer shards.
+    ///
+    /// Returns None if a write mutex is poisoned, or if hashtable is built with lock free writes.
+    pub fn wrlock(&self) -> Option<RcuHtWriter<'_, '_, 'ht, K, V, S>> {
+        if self.ht.lock_free_writes {
+            return None;
+        }
+
+        // always lock shards in the same order, to avoid deadlocks between such writers
+        let mut guards = Vec::with_capacity(self.ht.shards.len());
+        for mutex in self.ht.shards.iter() {
+            match mutex.lock() {
+                Ok(guard) => guards.push(guard),
+                Err(_err) => return None,
+            }
+        }
+
+        Some(RcuHtWriter::new(self.ht.urcuht, self, None, guards))
+    }
+
+    /// Get a writer locking only the writer shard of `key`.
+    ///
+    /// Writers of different shards can run concurrently. This writer can only write keys of this
+    /// shard, and cannot perform operations on the whole hashtable (drain, retain...): it panics
+    /// otherwise. With a single shard (default), this is the same as [`wrlock`](Self::wrlock).
+    ///
+    /// Returns None if the write mutex is poisoned, or if hashtable is built with lock free writes.
+    pub fn wrlock_for<Q>(&self, key: &Q) -> Option<RcuHtWriter<'_, '_, 'ht, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if self.ht.shards.len() == 1 || self.ht.lock_free_writes {
+            return self.wrlock();
+        }
+
+        let shard = self.ht.shard_of(self.ht.key_hash(key));
+
+        match self.ht.shards[shard].lock() {
+            Ok(guard) => Some(RcuHtWriter::new(
+                self.ht.urcuht,
+                self,
+                Some(shard),
+                vec![guard],
+            )),
+            Err(_err) => None,
+        }
+    }
+
+    /// Returns true if a writer currently holds a write mutex (of any shard), for diagnostics.
+    ///
+    /// Nothing is waited for: free mutexes are only taken and released at once. The result may be
+    /// outdated as soon as it is returned. It is always false with lock free writes.
+    pub fn writer_contended(&self) -> bool {
+        self.ht
+            .shards
+            .iter()
+            .any(|mutex| matches!(mutex.try_lock(), Err(TryLockError::WouldBlock)))
+    }
+
+    /// Get a writer for the whole hashtable from an async task (`tokio` feature), without blocking
+    /// the executor thread.
+    ///
+    /// Async writers queue for their turn, then the first one tries to lock all writer shards. If
+    /// another writer holds one of them, shards are released and the task sleeps until a writer
+    /// drops its locks, then tries again.
+    ///
+    /// The future borrows this per thread handle (registered in urcu lib), so it cannot be moved
+    /// to another thread: it must be awaited on a thread that stays registered, for instance in a
+    /// current thread runtime or a `tokio::task::LocalSet`.
+    /// It resolves to None in the same cases as [`wrlock`](Self::wrlock).
+    ///
+    /// ```compile_fail
+    /// let ht = urcu_ht::RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+    /// let thread = ht.thread();
+    /// let future = thread.wrlock_async();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(move || drop(future));
+    /// });
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn wrlock_async(&self) -> Option<RcuHtWriter<'_, '_, 'ht, K, V, S>> {
+        if self.ht.lock_free_writes {
+            return None;
+        }
+
+        let queue = &self.ht.async_writers;
+        let _turn = queue.turn.lock().await;
+
+        loop {
+            // registered before trying: a writer released meanwhile wakes this task
+            let mut released = std::pin::pin!(queue.released.notified());
+            released.as_mut().enable();
+
+            // always lock shards in the same order, and never keep some of them locked while waiting
+            let mut guards = Vec::with_capacity(self.ht.shards.len());
+            for mutex in self.ht.shards.iter() {
+                match mutex.try_lock() {
+                    Ok(guard) => guards.push(guard),
+                    Err(TryLockError::WouldBlock) => break,
+                    Err(TryLockError::Poisoned(_err)) => return None,
+                }
+            }
+
+            if guards.len() == self.ht.shards.len() {
+                return Some(RcuHtWriter::new(self.ht.urcuht, self, None, guards));
+            }
+
+            drop(guards);
+            released.await;
+        }
+    }
+
+    /// Get a writer which does not lock any mutex, if hashtable is built with lock free writes.
+    ///
+    /// Returns None otherwise: it would break the guarantees of mutex based writers.
+    pub fn concurrent_writer(&self) -> Option<RcuHtConcurrentWriter<'_, 'ht, K, V, S>> {
+        if !self.ht.lock_free_writes {
+            return None;
+        }
+
+        Some(RcuHtConcurrentWriter {
+            urcuht: self.ht.urcuht,
+            thread: self,
+        })
+    }
+
+    pub fn rdlock(&self) -> RcuHtRead<'_, 'ht, K, V, S> {
+        RcuHtRead::new(self.ht.urcuht, self)
+    }
+
+    /// Get the value indexed by `key`, bundled with the read lock protecting it.
+    ///
+    /// Unlike [`RcuHtRead::get`], the returned guard is bound to this thread handle only, so it
+    /// can be moved up the stack out of the function doing the lookup. The read lock is released
+    /// when the guard is dropped.
+    pub fn get_guard<Q>(&self, key: &Q) -> Option<RcuHtGuard<'_, 'ht, K, V, S>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let rdlock = self.rdlock();
+        let value: *const V = rdlock.get(key)?;
+
+        Some(RcuHtGuard {
+            _rdlock: rdlock,
+            value,
+        })
+    }
+
+    #[cfg(feature = "qsbr")]
+    pub fn quiescent_state(&self) {
+        flavor::quiescent_state();
+    }
+
+    /// Put this thread offline (QSBR): grace periods do not wait for it anymore.
+    ///
+    /// Call it before blocking for a long time (e.g. on I/O), so writers waiting for a grace
+    /// period are not stalled. Reading the hashtable (or using a reference obtained before) while
+    /// offline is undefined behavior: call [`online`](Self::online) first.
+    #[cfg(feature = "qsbr")]
+    pub fn offline(&self) {
+        flavor::thread_offline();
+    }
+
+    /// Put this thread back online (QSBR), after [`offline`](Self::offline), before reading again.
+    #[cfg(feature = "qsbr")]
+    pub fn online(&self) {
+        flavor::thread_online();
+    }
+}
+
+impl<'ht, K, V, S> Drop for RcuHtThread<'ht, K, V, S> {
+    fn drop(&mut self) {
+        urcu_thread_leave(self.counted);
+    }
+}
+
+/// Async writers waiting for write mutexes, see [`RcuHtThread::wrlock_async`].
+#[cfg(feature = "tokio")]
+#[derive(Default)]
+struct AsyncWriterQueue {
+    /// only the async writer holding it tries to lock writer shards, so async writers never wait
+    /// for shards partially locked by each other.
+    turn: tokio::sync::Mutex<()>,
+    /// notified each time a writer releases its locks.
+    released: tokio::sync::Notify,
+}
+
+/// Register the current thread if this is its first handle.
+/// Returns true if the handle is accounted in thread reference counter (when registration is not
+/// managed by the caller).
+/// It panics rather than letting the counter wrap to 0, as the thread would then be unregistered
+/// while still used by other handles.
+fn urcu_thread_enter() -> bool {
+    // thread lifecycle is managed by the caller
+    if URCU_THREAD_EXTERNAL_REGISTRATION.with(|cell| cell.get()) {
+        return false;
+    }
+
+    // manage thread reference counter : if the count is 1 => register this thread
+    let thread_count = URCU_THREAD_REGISTERED_COUNT.with(|cell| {
+        let thread_count = cell
+            .get()
+            .checked_add(1)
+            .expect("Too many RcuHtThread handles on this thread");
+        cell.set(thread_count);
+        thread_count
+    });
+
+    // the thread may still be registered by previous handles (manual_thread_lifecycle feature)
+    if thread_count == 1
+        && !URCU_THREAD_AUTO_REGISTRATION.with(|registration| registration.replace(true))
+    {
+        flavor::register_thread();
+    }
+
+    true
+}
+
+/// Unregister the current thread if this is its last handle.
+/// `counted` is the value returned by `urcu_thread_enter`.
+fn urcu_thread_leave(counted: bool) {
+    if !counted {
+        return;
+    }
+
+    /* manage thread reference counter : if the count is 0 (last object) => unregister this thread */
+    let thread_count = URCU_THREAD_REGISTERED_COUNT.with(|cell| {
+        let mut thread_count = cell.get();
+        thread_count -= 1;
+        cell.set(thread_count);
+        thread_count
+    });
+
+    // with manual_thread_lifecycle feature, the thread stays registered until it exits or
+    // unregister_current_thread is called
+    if thread_count == 0 && !cfg!(feature = "manual_thread_lifecycle") {
+        urcu_thread_auto_unregister();
+    }
+}
+
+/// Unregister the current thread if it is registered by handles. No handle must be alive.
+fn urcu_thread_auto_unregister() {
+    if URCU_THREAD_AUTO_REGISTRATION.with(|registration| registration.replace(false)) {
+        urcu_defer_thread_leave();
+        flavor::unregister_thread();
+    }
+}
+
+/// Register the current thread for defer_rcu, if not already done. Returns false on failure.
+/// It stays registered until the thread is unregistered from urcu lib.
+fn urcu_defer_thread_enter() -> bool {
+    if URCU_THREAD_DEFER_REGISTERED.with(|cell| cell.get()) {
+        return true;
+    }
+
+    let registered = flavor::defer_register_thread();
+    URCU_THREAD_DEFER_REGISTERED.with(|cell| cell.set(registered));
+    registered
+}
+
+/// Unregister the current thread for defer_rcu, if it is registered.
+/// Objects still queued by this thread are free'd first.
+fn urcu_defer_thread_leave() {
+    if URCU_THREAD_DEFER_REGISTERED.with(|cell| cell.replace(false)) {
+        flavor::defer_unregister_thread();
+    }
+}
+
+/// Record a hashtable operation, when `metrics` feature is enabled.
+///
+/// A single counter is used, labelled with the operation type: "insert", "remove", "lookup_hit",
+/// "lookup_miss" or "deferred_free".
+fn urcu_metrics_record(op: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("urcu_ht_operations", "op" => op).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = op;
+}
+
+/// Number of counter shards used to count lookup hits and misses, when `stats` feature is enabled.
+#[cfg(feature = "stats")]
+const HIT_MISS_SHARDS: usize = 64;
+
+/// Lookup hit and miss counters of a group of threads, on their own cache line.
+#[cfg(feature = "stats")]
+#[derive(Default)]
+#[repr(align(64))]
+struct HitMissShard {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Get the hit and miss counter shard of the current thread.
+/// Threads are spread over shards in the order they do their first lookup.
+#[cfg(feature = "stats")]
+fn urcu_hit_miss_shard() -> usize {
+    static NEXT_SHARD: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    thread_local! {
+        static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % HIT_MISS_SHARDS;
+    }
+
+    SHARD.with(|shard| *shard)
+}
+
+/// Record a lookup result, when `metrics` feature is enabled.
+fn urcu_metrics_record_lookup<T>(ret: Option<T>) -> Option<T> {
+    urcu_metrics_record(match ret {
+        Some(_) => "lookup_hit",
+        None => "lookup_miss",
+    });
+    ret
+}
+
+pub struct RcuHtRead<'thread, 'ht, K, V, S = WyHashBuilder> {
+    urcuht: *mut urcu_sys::cds_lfht,
+    thread: &'thread RcuHtThread<'ht, K, V, S>,
+    /// read lock is held by the caller, not by this object (see [`RcuHtRead::from_external_lock`])
+    external_lock: bool,
+    /// read lock was already released by [`RcuHtRead::release`]
+    released: bool,
+}
+
+impl<'rdlock, 'thread, 'ht, K, V, S> RcuHtRead<'thread, 'ht, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Get a new "read" handle.
+    /// A different handle is needed for each thread doing "read" operations.
+    /// It registers this thread in urcu lib.
+    /// It must stick to a single thread. One must not try to move this handle between threads.
+    pub fn new(
+        urcuht: *mut urcu_sys::cds_lfht,
+        thread: &'thread RcuHtThread<'ht, K, V, S>,
+    ) -> Self {
+        flavor::read_lock();
+
+        RcuHtRead {
+            urcuht,
+            thread,
+            external_lock: false,
+            released: false,
+        }
+    }
+
+    /// Get a "read" handle relying on a read lock already held by the caller.
+    ///
+    /// No read lock is taken here, nor released when the handle is dropped. This is meant for
+    /// embedders already holding a read lock (rcu_read_lock) from their own code.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must hold a read lock of the RCU flavor this crate is built with, from
+    /// before this call until the returned handle and all references obtained from it are dropped.
+    pub unsafe fn from_external_lock(
+        urcuht: *mut urcu_sys::cds_lfht,
+        thread: &'thread RcuHtThread<'ht, K, V, S>,
+    ) -> Self {
+        RcuHtRead {
+            urcuht,
+            thread,
+            external_lock: true,
+            released: false,
+        }
+    }
+
+    /// Release the read lock now, instead of at the end of the scope.
+    ///
+    /// It narrows the read-side critical section: grace periods (and so reclamation of values
+    /// removed by writers) are no longer delayed by this handle.
+    pub fn release(mut self) {
+        if !self.external_lock {
+            flavor::read_unlock();
+        }
+        self.released = true;
+    }
+
+    pub fn get<Q>(&'rdlock self, key: &Q) -> Option<&'rdlock V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get_with_hash(key, self.thread.ht.key_hash(key))
+    }
+
+    /// Get a reference to the value indexed by `key`, as [`get`](Self::get), with a miss reported
+    /// as `Err(())`.
+    ///
+    /// This lets lookups short-circuit functions returning a `Result` with `?`.
+    #[allow(clippy::result_unit_err)]
+    pub fn get_or_miss<Q>(&'rdlock self, key: &Q) -> Result<&'rdlock V, ()>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).ok_or(())
+    }
+
+    /// Get the value indexed by `key` as a borrowed Cow, bound to this read lock.
+    ///
+    /// Callers needing the value after the read lock is released can clone it on demand
+    /// (`Cow::into_owned`), others just borrow it.
+    pub fn get_cow<Q>(&'rdlock self, key: &Q) -> Option<std::borrow::Cow<'rdlock, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: Clone,
+    {
+        self.get(key).map(std::borrow::Cow::Borrowed)
+    }
+
+    /// Get a reference to the value indexed by `key`, whose hash is already known.
+    ///
+    /// `hash` must be the hash of `key` computed by the hasher of the hashtable
+    /// ([`RcuHt::hasher`]), as returned by [`iter_with_hash`](Self::iter_with_hash) for instance.
+    /// With any other value, the object is most likely not found.
+    pub fn get_with_hash<Q>(&'rdlock self, key: &Q, hash: u64) -> Option<&'rdlock V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        self.lookup(key, hash).map(|node| unsafe { &(*node).data })
+    }
+
+    /// helper function to look up the node indexed by `key`, counting the hit or miss for the
+    /// `stats` and `metrics` features. Every lookup of a single key goes through it.
+    fn lookup<Q>(&'rdlock self, key: &Q, hash: u64) -> Option<*mut RcuLfhtNode<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Eq,
+    {
+        let mut ret = None;
+
+        unsafe {
+            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, hash, key);
+
+            if !found_node.is_null() {
+                ret = Some(urcu_cds_lfht_node_to_rust_type::<K, V>(found_node));
+            }
+        }
+
+        #[cfg(feature = "stats")]
+        self.thread.ht.record_hit_miss(ret.is_some());
+
+        urcu_metrics_record_lookup(ret)
+    }
+
+    /// Get a reference to the value indexed by `key`, as [`get`](Self::get) does.
+    ///
+    /// `Ok(None)` is a genuine miss. urcu lib lookups stay correct during a resize, so this
+    /// currently never fails: the error case is reserved for conditions (e.g. contention) that
+    /// could be reported by future versions.
+    pub fn try_get<Q>(&'rdlock self, key: &Q) -> Result<Option<&'rdlock V>, RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        Ok(self.get(key))
+    }
+
+    /// Get references to the stored key and value indexed by `key`.
+    ///
+    /// Stored key may be a different instance than the lookup key.
+    pub fn get_key_value<Q>(&'rdlock self, key: &Q) -> Option<(&'rdlock K, &'rdlock V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.lookup(key, self.thread.ht.key_hash(key))
+            .map(|node| unsafe { (&(*node).key, &(*node).data) })
+    }
+
+    /// Perform many lookups under this single read lock.
+    ///
+    /// Results are returned in the same order as `keys`, a duplicated key gets its own result.
+    pub fn get_many<Q>(&'rdlock self, keys: &[&Q]) -> Vec<Option<&'rdlock V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        keys.iter().map(|key| self.get(*key)).collect()
+    }
+
+    /// Returns true if all `keys` are in the hashtable (true for an empty slice).
+    ///
+    /// Lookups are done under this single read lock, and stop at the first missing key.
+    pub fn contains_all<Q>(&'rdlock self, keys: &[&Q]) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        keys.iter().all(|key| self.get(*key).is_some())
+    }
+
+    /// Returns true if at least one of `keys` is in the hashtable (false for an empty slice).
+    ///
+    /// Lookups are done under this single read lock, and stop at the first key found.
+    pub fn contains_any<Q>(&'rdlock self, keys: &[&Q]) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        keys.iter().any(|key| self.get(*key).is_some())
+    }
+
+    /// Copy all objects of the hashtable into an owned std HashMap.
+    ///
+    /// All keys and values are cloned (O(n)) under this read lock: with concurrent writers, the copy
+    /// is not an atomic snapshot.
+    pub fn to_hashmap(&'rdlock self) -> std::collections::HashMap<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Get an iterator over all objects of the hashtable, sorted by key.
+    ///
+    /// All references are first collected in a temporary vector, then sorted: this costs
+    /// O(n log n) and O(n) memory. Items are bound to this read lock, held for the whole iteration.
+    pub fn iter_sorted(&'rdlock self) -> impl Iterator<Item = (&'rdlock K, &'rdlock V)>
+    where
+        K: Ord,
+    {
+        let mut pairs: Vec<_> = self.iter().collect();
+        pairs.sort_unstable_by_key(|(key, _)| *key);
+        pairs.into_iter()
+    }
+
+    /// Get an iterator over clones of all objects of the hashtable.
+    ///
+    /// Items are owned, so they can be kept after this read lock is released. The walk itself
+    /// still borrows this read lock.
+    pub fn iter_cloned(&'rdlock self) -> impl Iterator<Item = (K, V)> + 'rdlock
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.iter().map(|(key, value)| (key.clone(), value.clone()))
+    }
+
+    /// Get all objects whose key is in `range`.
+    ///
+    /// Hashtable is not ordered: the whole hashtable is walked (O(n)) to filter keys, this is
+    /// meant for occasional range queries. Objects are returned in the internal hashtable order,
+    /// not sorted by key.
+    pub fn get_range<R>(&'rdlock self, range: R) -> Vec<(&'rdlock K, &'rdlock V)>
+    where
+        K: Ord,
+        R: std::ops::RangeBounds<K>,
+    {
+        self.iter()
+            .filter(|(key, _)| range.contains(*key))
+            .collect()
+    }
+
+    /// Count objects for which the predicate `f` returns true.
+    ///
+    /// The whole hashtable is walked under the read lock, without collecting anything.
+    pub fn count_where<F>(&'rdlock self, mut f: F) -> usize
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.iter().filter(|(key, value)| f(key, value)).count()
+    }
+
+    /// Count objects indexed by `key`: 0 or 1, unless duplicates were added with
+    /// [`RcuHtWriter::insert_duplicate`].
+    pub fn count_key<Q>(&'rdlock self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let mut count = 0;
+
+        unsafe {
+            let mut iter = urcu_lookup::<Q, K, V>(self.urcuht, self.thread.ht.key_hash(key), key);
+
+            while !urcu_sys::cds_lfht_iter_get_node(&mut iter).is_null() {
+                count += 1;
+                urcu_next_duplicate::<Q, K, V>(self.urcuht, key, &mut iter);
+            }
+        }
+
+        count
+    }
+
+    /// Get the first object found in the hashtable (using `cds_lfht_first`), or None if it is empty.
+    ///
+    /// Hashtable is not ordered: "first" means an arbitrary object, not the smallest key.
+    pub fn first(&'rdlock self) -> Option<(&'rdlock K, &'rdlock V)> {
+        self.iter().next()
+    }
+
+    /// Get the approximate number of objects given by urcu lib's per-CPU split counters, read
+    /// just before and just after walking the hashtable to count objects.
+    ///
+    /// Both values are the same (and exact) without concurrent writers: a difference shows
+    /// writes done during the walk. Counters are signed, as per-CPU sums may be briefly
+    /// negative. Returns None if hashtable was not built with [`RcuHtBuilder::accounting`].
+    pub fn split_counts(&self) -> Option<(i64, i64)> {
+        if !self.thread.ht.accounting {
+            return None;
+        }
+
+        let (before, _, after) = unsafe { urcu_count_nodes_split(self.urcuht) };
+        Some((before, after))
+    }
+
+    /// Get the approximate load factor of the hashtable: number of objects per bucket.
+    ///
+    /// It is approximate when writers are running concurrently. The number of buckets is
+    /// the one requested at creation or by an explicit resize: automatic resizes are not seen.
+    pub fn load_factor(&self) -> f64 {
+        let count = unsafe { urcu_count_nodes(self.urcuht) };
+        count as f64 / self.bucket_count() as f64
+    }
+
+    /// Get an approximate memory footprint of the hashtable, in bytes.
+    ///
+    /// It counts the nodes of all objects, the nodes of removed objects still waiting for a grace
+    /// period, and the bucket array (with the same approximation as
+    /// [`bucket_count`](Self::bucket_count)). Heap memory owned by keys and values (the buffer of
+    /// a String for instance) and free'd nodes kept in the node pool are not counted.
+    pub fn memory_estimate(&self) -> usize {
+        let count = unsafe { urcu_count_nodes(self.urcuht) } as usize;
+        let nodes = count + self.thread.ht.pending_reclaims();
+        let buckets = self.bucket_count() as usize;
+
+        nodes * std::mem::size_of::<RcuLfhtNode<K, V>>()
+            + buckets * std::mem::size_of::<urcu_sys::cds_lfht_node>()
+    }
+
+    /// Returns true if the load factor is above `threshold` objects per bucket.
+    ///
+    /// It uses the same approximation as [`load_factor`](Self::load_factor): automatic resizes
+    /// are not seen, so an autoresizing hashtable may be reported overloaded although urcu lib
+    /// keeps up. Still, it is a useful signal under heavy insert bursts.
+    pub fn is_overloaded(&self, threshold: f64) -> bool {
+        self.load_factor() > threshold
+    }
+
+    /// Get the number of buckets requested at creation, or by the last explicit resize
+    /// ([`RcuHtWriter::shrink_to_fit`]). Automatic resizes done by urcu lib are not seen.
+    pub fn bucket_count(&self) -> u64 {
+        self.thread.ht.bucket_count.load(Ordering::Relaxed)
+    }
+
+    /// Split the hashtable into `n` iterators covering disjoint parts of the hash space.
+    ///
+    /// Each object is returned by exactly one of these iterators, so they can be consumed
+    /// in parallel (for instance using std::thread::scope). They all share this read lock,
+    /// which must be held until all of them are dropped.
+    /// Each iterator walks the table from its beginning up to the end of its own range,
+    /// skipping objects of previous ranges. At least one iterator is returned.
+    pub fn par_chunks(&'rdlock self, n: usize) -> Vec<RcuHtIter<'rdlock, K, V>> {
+        let n = n.max(1) as u128;
+        let space = 1u128 << 64;
+
+        (0..n)
+            .map(|i| {
+                let start = (i * space / n) as u64;
+                let end = match i + 1 {
+                    last if last == n => None,
+                    next => Some((next * space / n) as u64),
+                };
+                RcuHtIter::with_range(self.urcuht, start, end)
+            })
+            .collect()
+    }
+
+    /// Get an iterator starting at the object indexed by `key`, then going on with the following
+    /// objects of the hashtable.
+    ///
+    /// Objects are walked in the internal hashtable order (by hash, not by key), so a scan can be
+    /// resumed from the last key seen. The iterator is empty if `key` is not found (it may have
+    /// been removed since): resuming then needs a new scan.
+    pub fn iter_from<Q>(&'rdlock self, key: &Q) -> RcuHtIter<'rdlock, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        unsafe {
+            let iter = urcu_lookup::<Q, K, V>(self.urcuht, self.thread.ht.key_hash(key), key);
+            RcuHtIter::with_iter(self.urcuht, iter)
+        }
+    }
+
+    /// Get an iterator over all objects of the hashtable, with the hash of their key.
+    ///
+    /// The hash is the one computed at insert time by the hasher of the hashtable (wyhash by
+    /// default), read back from the node: objects can be moved to another table using the
+    /// same hasher without hashing keys again.
+    pub fn iter_with_hash(&'rdlock self) -> RcuHtHashIter<'rdlock, K, V> {
+        RcuHtHashIter { iter: self.iter() }
+    }
+
+    /// Get an iterator over all objects of the hashtable. Items are bound to this read lock.
+    ///
+    /// Concurrent writes (and resizes) are allowed during the iteration:
+    /// - yielded references stay valid until the read lock is released, even if their objects
+    ///   are removed or replaced meanwhile (they are only free'd after a grace period),
+    /// - objects present during the whole iteration are yielded exactly once,
+    /// - objects inserted or removed during the iteration may or may not be yielded,
+    /// - an object replaced during the iteration is yielded once, with its old or new value.
+    ///
+    /// So this is not an atomic snapshot of the hashtable. Objects are yielded in no particular
+    /// order.
+    pub fn iter(&'rdlock self) -> RcuHtIter<'rdlock, K, V> {
+        RcuHtIter::new(self.urcuht)
+    }
+}
+
+impl<'rdlock, 'thread, 'ht, K, T, S> RcuHtRead<'thread, 'ht, K, std::sync::Arc<T>, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Get an owned handle on the shared value indexed by `key`.
+    ///
+    /// Only the reference counter is incremented under the read lock: the returned Arc can
+    /// outlive the read lock, while the value itself is never copied.
+    pub fn get_arc<Q>(&'rdlock self, key: &Q) -> Option<std::sync::Arc<T>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).cloned()
+    }
+}
+
+impl<'thread, 'ht, K, Q, V, S> std::ops::Index<&Q> for RcuHtRead<'thread, 'ht, K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: ?Sized + Hash + Eq,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Get a reference to the value indexed by `key`, bound to this read lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not in the hashtable.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in hashtable")
+    }
+}
+
+impl<'thread, 'ht, K, V, S> Drop for RcuHtRead<'thread, 'ht, K, V, S> {
+    fn drop(&mut self) {
+        if !self.external_lock && !self.released {
+            flavor::read_unlock();
+        }
+    }
+}
+
+/// A value of the hashtable and the read lock keeping it alive, see [`RcuHtThread::get_guard`].
+///
+/// It gives access to the value (Deref) until it is dropped.
+pub struct RcuHtGuard<'thread, 'ht, K, V, S = WyHashBuilder> {
+    // value cannot be free'd while this read lock is held
+    _rdlock: RcuHtRead<'thread, 'ht, K, V, S>,
+    value: *const V,
+}
+
+impl<'thread, 'ht, K, V, S> std::ops::Deref for RcuHtGuard<'thread, 'ht, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { &*self.value }
+    }
+}
+
+/// Iterator over all objects of a hashtable (or over a range of it, see [`RcuHtRead::par_chunks`]).
+///
+/// It must be created under a read lock and it cannot live longer than that lock.
+pub struct RcuHtIter<'rdlock, K, V> {
+    urcuht: *mut urcu_sys::cds_lfht,
+    iter: urcu_sys::cds_lfht_iter,
+    /// first reverse hash value of the range covered by this iterator
+    start: u64,
+    /// end (excluded) of the covered range of reverse hash values, None means "up to the end of the table"
+    end: Option<u64>,
+    phantom: PhantomData<&'rdlock (K, V)>,
+}
+
+/// A sub-iterator can be moved to another thread as long as the read lock it borrows is held
+/// by the thread which created it: nodes cannot be reclaimed before this read lock is released.
+unsafe impl<'rdlock, K: Sync, V: Sync> Send for RcuHtIter<'rdlock, K, V> {}
+
+impl<'rdlock, K, V> RcuHtIter<'rdlock, K, V> {
+    /// This function must be called with rcu_read_lock held.
+    fn new(urcuht: *mut urcu_sys::cds_lfht) -> Self {
+        Self::with_range(urcuht, 0, None)
+    }
+
+    /// Iterator starting at the node of an iterator already positioned (lookup).
+    /// This function must be called with rcu_read_lock held.
+    fn with_iter(urcuht: *mut urcu_sys::cds_lfht, iter: urcu_sys::cds_lfht_iter) -> Self {
+        RcuHtIter {
+            urcuht,
+            iter,
+            start: 0,
+            end: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Iterator over nodes whose reverse hash is in [start, end).
+    /// This function must be called with rcu_read_lock held.
+    fn with_range(urcuht: *mut urcu_sys::cds_lfht, start: u64, end: Option<u64>) -> Self {
+        unsafe {
+            let mut iter: urcu_sys::cds_lfht_iter = std::mem::MaybeUninit::zeroed().assume_init();
+
+            // cds_lfht_first - get the first node in the table.
+            // Output in "*iter". *iter->node set to NULL if table is empty.
+            urcu_sys::cds_lfht_first(urcuht, &mut iter as *mut urcu_sys::cds_lfht_iter);
+
+            RcuHtIter {
+                urcuht,
+                iter,
+                start,
+                end,
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    /// Get current "node" pointer and move to the next one. Returns NULL at the end of the range.
+    /// Current node can be deleted (cds_lfht_del) before moving to the next one.
+    fn next_node(&mut self) -> *mut urcu_sys::cds_lfht_node {
+        self.next_iter().node
+    }
+
+    /// Get an iterator positioned on the current node and move to the next one, as
+    /// [`next_node`](Self::next_node). Its node is NULL at the end of the range.
+    /// Current node can be replaced (cds_lfht_replace) with the returned iterator.
+    fn next_iter(&mut self) -> urcu_sys::cds_lfht_iter {
+        unsafe {
+            loop {
+                let current = self.iter;
+                let found_node = urcu_sys::cds_lfht_iter_get_node(&mut self.iter);
+
+                if found_node.is_null() {
+                    return current;
+                }
+
+                // Nodes are sorted by reverse hash in urcu hashtable (split-ordered list),
+                // so we can stop as soon as we are after the end of the range.
+                let reverse_hash = (*found_node).reverse_hash;
+                if let Some(end) = self.end {
+                    if reverse_hash >= end {
+                        self.iter.node = std::ptr::null_mut();
+                        return self.iter;
+                    }
+                }
+
+                // cds_lfht_next - get the next node in the table.
+                // *iter->node set to NULL if no following node exists.
+                urcu_sys::cds_lfht_next(
+                    self.urcuht,
+                    &mut self.iter as *mut urcu_sys::cds_lfht_iter,
+                );
+
+                if reverse_hash >= self.start {
+                    return current;
+                }
+            }
+        }
+    }
+}
+
+impl<'rdlock, K, V> Iterator for RcuHtIter<'rdlock, K, V> {
+    type Item = (&'rdlock K, &'rdlock V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found_node = self.next_node();
+
+        if found_node.is_null() {
+            return None;
+        }
+
+        unsafe {
+            let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+            Some((&(*node).key, &(*node).data))
+        }
+    }
+}
+
+/// Iterator over all objects of a hashtable with the hash of their key, see [`RcuHtRead::iter_with_hash`].
+pub struct RcuHtHashIter<'rdlock, K, V> {
+    iter: RcuHtIter<'rdlock, K, V>,
+}
+
+impl<'rdlock, K, V> Iterator for RcuHtHashIter<'rdlock, K, V> {
+    type Item = (&'rdlock K, &'rdlock V, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let found_node = self.iter.next_node();
+
+        if found_node.is_null() {
+            return None;
+        }
+
+        unsafe {
+            // urcu lib stores the bit-reversed hash in its node (split-ordered list)
+            let hash = (*found_node).reverse_hash.reverse_bits();
+            let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+            Some((&(*node).key, &(*node).data, hash))
+        }
+    }
+}
+
+pub struct RcuHtWriterGuard<K, V> {
+    phantom_key: PhantomData<K>,
+    phantom_val: PhantomData<V>,
+}
+
+impl<K, V> RcuHtWriterGuard<K, V> {
+    fn new() -> Self {
+        RcuHtWriterGuard {
+            phantom_key: PhantomData,
+            phantom_val: PhantomData,
+        }
+    }
+}
+
+/// An operation on a hashtable, applied by [`RcuHtWriter::apply_op`] (`fuzzing` feature only).
+///
+/// Sequences of operations can be replayed against both a hashtable and a reference map
+/// (`std::collections::HashMap`), to compare their results.
+#[cfg(feature = "fuzzing")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op<K, V> {
+    /// Insert or replace an object.
+    Insert(K, V),
+    /// Remove an object.
+    Remove(K),
+    /// Get the value of an object.
+    Get(K),
+    /// Remove all objects.
+    Clear,
+    /// Wait until all removed objects are free'd.
+    FlushReclamation,
+}
+
+/// Writer object used to perform safe add and del operations.
+///
+/// It can only be called under locked mutex to protect from concurrent access.
+/// It must not be shared between threads.
+///
+/// A writer locks all writer shards ([`RcuHtThread::wrlock`]) or a single one
+/// ([`RcuHtThread::wrlock_for`]): in the latter case, it can only access keys of this shard.
+pub struct RcuHtWriter<'guard, 'thread, 'ht, K, V, S = WyHashBuilder> {
+    urcuht: *mut urcu_sys::cds_lfht,
+    // keep references to thread so object cannot be destroyed in an invalid order
+    _thread: &'thread RcuHtThread<'ht, K, V, S>,
+    // writer shard locked by this writer, None if all shards are locked
+    shard: Option<usize>,
+    // have the guards here so locks will be released when writer is destroyed
+    _guards: Vec<MutexGuard<'guard, RcuHtWriterGuard<K, V>>>,
+}
+
+/// Wake the async writer waiting for write mutexes, if any, once they are released.
+#[cfg(feature = "tokio")]
+impl<'guard, 'thread, 'ht, K, V, S> Drop for RcuHtWriter<'guard, 'thread, 'ht, K, V, S> {
+    fn drop(&mut self) {
+        self._guards.clear();
+        self._thread.ht.async_writers.released.notify_waiters();
+    }
+}
+
+impl<'guard, 'thread, 'ht, K, V, S> RcuHtWriter<'guard, 'thread, 'ht, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Creates a write instance.
+    ///
+    /// There should be only one single instance allocated under the write mutex.
+    fn new(
+        urcuht: *mut urcu_sys::cds_lfht,
+        thread: &'thread RcuHtThread<'ht, K, V, S>,
+        shard: Option<usize>,
+        guards: Vec<MutexGuard<'guard, RcuHtWriterGuard<K, V>>>,
+    ) -> RcuHtWriter<'guard, 'thread, 'ht, K, V, S> {
+        // return an object containing the pointer to the hashtable
+        RcuHtWriter {
+            urcuht,
+            _thread: thread,
+            shard,
+            _guards: guards,
+        }
+    }
+
+    /// Compute the hash of `key`, checking that it belongs to the shard locked by this writer.
+    fn locked_key_hash<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
+        let ht = self._thread.ht;
+        let hash = ht.key_hash(key);
+
+        if let Some(shard) = self.shard {
+            assert_eq!(
+                ht.shard_of(hash),
+                shard,
+                "key is not in the writer shard locked by this writer"
+            );
+        }
+
+        hash
+    }
+
+    /// Check that this writer locks all writer shards.
+    fn check_all_shards_locked(&self) {
+        assert!(
+            self.shard.is_none(),
+            "operation on the whole hashtable needs a writer locking all shards (wrlock)"
+        );
+    }
+
+    /// Add or replace an existing key/value.
+    ///
+    /// Parameters (key and value) are moved in hashtable.
+    /// Main difference with standard collection HashMap : we cannot return/move existing value here.
+    /// We must destroy it after a grace period. If it were returned by this function, it could be deleted immediately
+    ///
+    /// With [`ReclaimMode::Synchronous`], this function waits for a grace period when a value is
+    /// replaced: it must not be called while a read lock is held by this thread.
+    pub fn insert_or_replace(&mut self, key: K, value: V) {
+        let h = self.locked_key_hash(&key);
+
+        unsafe {
+            let val = self._thread.ht.alloc_node(key, value);
+            urcu_replace_node(self.urcuht, h, val, self._thread.ht.reclaim_mode);
+            // the new object cannot be removed by another writer while this one is locked
+            self._thread.ht.notify_insert(val);
+            self._thread.ht.evict_over_capacity();
+        }
+    }
+
+    /// Insert or replace an object, as [`insert_or_replace`](Self::insert_or_replace), and get a
+    /// reference to the stored value.
+    ///
+    /// As for [`get`](Self::get), the reference stays valid as long as this writer is borrowed.
+    pub fn insert_ref(&mut self, key: K, value: V) -> &V {
+        let h = self.locked_key_hash(&key);
+        let ht = self._thread.ht;
+
+        unsafe {
+            let val = ht.alloc_node(key, value);
+            urcu_replace_node(self.urcuht, h, val, ht.reclaim_mode);
+            ht.notify_insert(val);
+
+            // The new object is the newest one: it is never evicted (capacity is at least 1).
+            ht.evict_over_capacity();
+
+            &(*val).data
+        }
+    }
+
+    /// Add or replace an existing key/value, without aborting on allocation failure.
+    ///
+    /// Behaves like [`insert_or_replace`](Self::insert_or_replace), except that key and value are
+    /// given back to the caller if the new node cannot be allocated.
+    pub fn try_insert_or_replace(&mut self, key: K, value: V) -> Result<(), (K, V)> {
+        let h = self.locked_key_hash(&key);
+
+        unsafe {
+            let val = self._thread.ht.try_alloc_node(key, value)?;
+            urcu_replace_node(self.urcuht, h, val, self._thread.ht.reclaim_mode);
+            self._thread.ht.notify_insert(val);
+            self._thread.ht.evict_over_capacity();
+        }
+
+        Ok(())
+    }
+
+    /// Add or replace an existing key/value, returning the replaced value.
+    ///
+    /// Unlike [`insert_or_replace`](Self::insert_or_replace), the replaced value is not free'd
+    /// later but moved out once a grace period has elapsed.
+    ///
+    /// This function waits for a grace period when a value is replaced: it must not be called while
+    /// a read lock is held by this thread.
+    pub fn swap(&mut self, key: K, value: V) -> Option<V> {
+        let h = self.locked_key_hash(&key);
+
+        unsafe {
+            let val = self._thread.ht.alloc_node(key, value);
+            let old_node = urcu_add_replace_node(self.urcuht, h, val);
+            self._thread.ht.notify_insert(val);
+
+            if old_node.is_null() {
+                self._thread.ht.evict_over_capacity();
+                return None;
+            }
+
+            // Wait for all readers which could still access replaced node
+            flavor::synchronize();
+
+            let (_key, value) = urcu_take_node(old_node);
+            Some(value)
+        }
+    }
+
+    /// Add a new key/value, only if the key is not already in hashtable.
+    ///
+    /// On conflict, existing object is left untouched and `value` is given back to the caller.
+    pub fn insert_if_absent(&mut self, key: K, value: V) -> Result<(), V> {
+        let h = self.locked_key_hash(&key);
+
+        unsafe {
+            let val = self._thread.ht.alloc_node(key, value);
+            urcu_insert_unique(self.urcuht, h, val)?;
+            self._thread.ht.notify_insert(val);
+            self._thread.ht.evict_over_capacity();
+        }
+
+        Ok(())
+    }
+
+    /// Add a new key/value, even if the key is already in hashtable (multimap).
+    ///
+    /// Objects sharing a key are all kept: [`RcuHtRead::count_key`] counts them and iterators
+    /// walk all of them. Functions working on a single key (get, insert_or_replace,
+    /// [`remove_one`](Self::remove_one)...) apply to the first object found for this key, while
+    /// [`remove`](Self::remove) deletes all of them.
+    pub fn insert_duplicate(&mut self, key: K, value: V) {
+        let h = self.locked_key_hash(&key);
+
+        unsafe {
+            let val = self._thread.ht.alloc_node(key, value);
+            urcu_add_node(self.urcuht, h, val);
+            self._thread.ht.notify_insert(val);
+            self._thread.ht.evict_over_capacity();
+        }
+    }
+
+    /// Delete the object indexed by `key` from the hashtable, and return its stored key and value.
+    ///
+    /// This function fails with NotFound if the key is not in hashtable. As for
+    /// [`swap`](Self::swap), we wait for a grace period before moving key and value out of the
+    /// deleted node: this thread must not hold a read lock while calling this function.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Result<(K, V), RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let h = self.locked_key_hash(key);
+
+        unsafe {
+            // RCU read-side lock must be held between lookup and removal.
+            flavor::read_lock();
+            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, h, key);
+            let err = match found_node.is_null() {
+                true => None,
+                false => Some(urcu_delete_node(self.urcuht, found_node)),
+            };
+            flavor::read_unlock();
+
+            match err {
+                None => Err(RcuError::NotFound),
+                Some(0) => {
+                    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                    self._thread.ht.notify_remove(node);
+
+                    // Wait for all readers which could still access the deleted node
+                    flavor::synchronize();
+
+                    Ok(urcu_take_node(node))
+                }
+                Some(err) => Err(RcuError::DeleteError(DeleteErrorKind::from_code(err))),
+            }
+        }
+    }
+
+    /// Insert or replace an object, only if the hashtable holds less than `max_entries` objects.
+    ///
+    /// Replacing an existing key is always allowed. Otherwise, when the hashtable is full, nothing
+    /// is inserted and key and value are given back to the caller. Objects are counted by walking
+    /// the whole hashtable, and the count is approximate with concurrent writers (other shards).
+    pub fn insert_bounded(&mut self, key: K, value: V, max_entries: usize) -> Result<(), (K, V)> {
+        if self.get(&key).is_none() {
+            let count = unsafe {
+                flavor::read_lock();
+                let count = urcu_count_nodes(self.urcuht);
+                flavor::read_unlock();
+                count
+            };
+
+            if count >= max_entries as u64 {
+                return Err((key, value));
+            }
+        }
+
+        self.insert_or_replace(key, value);
+        Ok(())
+    }
+
+    /// Delete the value indexed by the `key` from the hashtable.
+    ///
+    /// All objects sharing this key are deleted, if duplicates were added with
+    /// [`insert_duplicate`](Self::insert_duplicate): see [`remove_one`](Self::remove_one) to
+    /// delete only one of them.
+    ///
+    /// This function may fail if node is not found.
+    /// With [`ReclaimMode::Synchronous`], the value is free'd before this function returns.
+    pub fn remove<Q>(&mut self, key: &Q) -> Result<(), RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.remove_key(key, true)
+    }
+
+    /// Delete a single value indexed by the `key` (the first one found), leaving its duplicates
+    /// in hashtable.
+    ///
+    /// This function may fail if node is not found.
+    /// With [`ReclaimMode::Synchronous`], the value is free'd before this function returns.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Result<(), RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.remove_key(key, false)
+    }
+
+    /// helper function to delete the first value indexed by the `key`, and its duplicates if
+    /// `duplicates` is true, reporting a single removal.
+    fn remove_key<Q>(&mut self, key: &Q, duplicates: bool) -> Result<(), RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let h = self.locked_key_hash(key);
+
+        let ht = self._thread.ht;
+        unsafe {
+            urcu_remove::<Q, K, V>(self.urcuht, h, key, duplicates, ht.reclaim_mode, |key| {
+                ht.notify_change(&ChangeEvent::Remove { key })
+            })
+        }
+    }
+
+    /// Delete the value indexed by the `key`, only if the predicate `pred` returns true for it.
+    ///
+    /// Returns true if the value was removed, false if the predicate rejected it.
+    /// This function fails with NotFound if the key is not in hashtable.
+    pub fn remove_if<Q, F>(&mut self, key: &Q, pred: F) -> Result<bool, RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        F: FnOnce(&V) -> bool,
+    {
+        let h = self.locked_key_hash(key);
+
+        let ret;
+        // the removed node is free'd once out of the read-side critical section
+        let mut removed = UrcuReclaimGuard::new(self._thread.ht.reclaim_mode);
+
+        unsafe {
+            // RCU read-side lock must be held between lookup and removal, even if `pred` panics.
+            let rdlock = flavor::ReadLock::new();
+
+            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, h, key);
+
+            ret = if found_node.is_null() {
+                Err(RcuError::NotFound)
+            } else {
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                if pred(&(*node).data) {
+                    match urcu_delete_node(self.urcuht, found_node) {
+                        0 => {
+                            removed.nodes.push(node);
+                            self._thread.ht.notify_remove(node);
+                            Ok(true)
+                        }
+                        err => Err(RcuError::DeleteError(DeleteErrorKind::from_code(err))),
+                    }
+                } else {
+                    Ok(false)
+                }
+            };
+
+            drop(rdlock);
+        }
+
+        ret
+    }
+
+    /// Exact number of objects in the hashtable, counted by walking all of them (O(n)).
+    ///
+    /// Unlike the approximate count of urcu lib, nothing can change the hashtable during the walk:
+    /// this writer must lock all shards.
+    pub fn exact_len(&self) -> usize {
+        self.check_all_shards_locked();
+
+        self._thread.rdlock().iter().count()
+    }
+
+    /// Remove all objects from the hashtable.
+    ///
+    /// Objects are free'd after a grace period, see [`drain`](Self::drain) to get them back.
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Replace the whole content of the hashtable with the objects of `iter`.
+    ///
+    /// All objects are removed first (see [`clear`](Self::clear)), then new ones are inserted
+    /// one by one. This is not atomic for readers: they may see an empty or partially filled
+    /// hashtable meanwhile. Removed objects are free'd after a grace period.
+    pub fn replace_all<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.clear();
+
+        for (key, value) in iter {
+            self.insert_or_replace(key, value);
+        }
+    }
+
+    /// Copy all objects of another hashtable, seen through its read lock `other`, in this one.
+    ///
+    /// Objects of `other` replace the ones of this hashtable with the same key. As for
+    /// [`insert_or_replace`](Self::insert_or_replace), replaced objects are free'd after a grace
+    /// period. This writer must lock all shards: nothing is merged otherwise.
+    pub fn merge<S2>(&mut self, other: &RcuHtRead<'_, '_, K, V, S2>)
+    where
+        K: Clone,
+        V: Clone,
+        S2: BuildHasher,
+    {
+        self.check_all_shards_locked();
+
+        for (key, value) in other.iter() {
+            self.insert_or_replace(key.clone(), value.clone());
+        }
+    }
+
+    /// Keep only the objects for which the predicate `f` returns true, remove all others.
+    ///
+    /// Removed objects are free'd after a grace period.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.retain_nodes(|node| f(&node.key, &node.data));
+    }
+
+    /// Replace values of the objects indexed by `keys` for which `f` returns a new value, leave
+    /// others unchanged. Absent keys are skipped, and `f` is called for every duplicate of a key
+    /// (see [`insert_duplicate`](Self::insert_duplicate)).
+    ///
+    /// As for [`update_all`](Self::update_all), each updated object is replaced by a new one
+    /// (copy-on-write). Keys are updated one after the other: `f` sees values updated before in
+    /// the same call. Other writers never see a partial update, but readers may.
+    pub fn update_many<Q, F>(&mut self, keys: &[&Q], mut f: F)
+    where
+        K: Borrow<Q> + Clone,
+        Q: ?Sized + Hash + Eq,
+        F: FnMut(&K, &V) -> Option<V>,
+    {
+        let ht = self._thread.ht;
+        // replaced nodes are free'd once out of the read-side critical section, even if `f` panics
+        let mut replaced = UrcuReclaimGuard::new(ht.reclaim_mode);
+
+        for key in keys {
+            let h = self.locked_key_hash(*key);
+
+            unsafe {
+                let rdlock = flavor::ReadLock::new();
+
+                let mut iter = urcu_lookup::<Q, K, V>(self.urcuht, h, *key);
+                while !iter.node.is_null() {
+                    let mut found = iter;
+                    // move to the next duplicate first: replacing this node does not affect it
+                    urcu_next_duplicate::<Q, K, V>(self.urcuht, *key, &mut iter);
+
+                    let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found.node);
+                    if let Some(value) = f(&(*node).key, &(*node).data) {
+                        let val = ht.alloc_node((*node).key.clone(), value);
+
+                        // There is no concurrent writer for this key: it cannot be removed meanwhile.
+                        let old_node = urcu_replace_at(self.urcuht, &mut found, val);
+                        debug_assert_eq!(old_node, node);
+                        ht.notify_insert(val);
+                        replaced.nodes.push(old_node);
+                    }
+                }
+
+                drop(rdlock);
+            }
+        }
+    }
+
+    /// Replace values of all objects for which `f` returns a new value, leave others unchanged.
+    ///
+    /// Values are never modified in place (readers may still access them): each updated object
+    /// is replaced by a new one (copy-on-write), and old ones are free'd after a grace period.
+    pub fn update_all<F>(&mut self, mut f: F)
+    where
+        K: Clone,
+        F: FnMut(&K, &V) -> Option<V>,
+    {
+        self.check_all_shards_locked();
+
+        let ht = self._thread.ht;
+        // replaced nodes are free'd once out of the read-side critical section, even if `f` panics
+        let mut replaced = UrcuReclaimGuard::new(ht.reclaim_mode);
+
+        unsafe {
+            // RCU read-side lock must be held while walking the hashtable.
+            let rdlock = flavor::ReadLock::new();
+
+            let mut iter = RcuHtIter::<K, V>::new(self.urcuht);
+            loop {
+                let mut found = iter.next_iter();
+                if found.node.is_null() {
+                    break;
+                }
+
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found.node);
+                if let Some(value) = f(&(*node).key, &(*node).data) {
+                    // iterator is already on the next node: replacing this one does not affect it
+                    let val = ht.alloc_node((*node).key.clone(), value);
+
+                    // There is no concurrent writer: the node cannot be removed meanwhile.
+                    let old_node = urcu_replace_at(self.urcuht, &mut found, val);
+                    debug_assert_eq!(old_node, node);
+                    ht.notify_insert(val);
+                    replaced.nodes.push(old_node);
+                }
+            }
+
+            drop(rdlock);
+        }
+    }
+
+    /// Remove all objects inserted (or replaced) more than `ttl` ago.
+    ///
+    /// Removed objects are free'd after a grace period. Objects keep the time of their insertion:
+    /// reading them does not extend their lifetime.
+    #[cfg(feature = "ttl")]
+    pub fn remove_expired(&mut self, ttl: std::time::Duration) {
+        let now = std::time::Instant::now();
+        self.retain_nodes(|node| now.duration_since(node.inserted) <= ttl);
+    }
+
+    /// Keep only the nodes for which the predicate `f` returns true, remove all others.
+    fn retain_nodes<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&RcuLfhtNode<K, V>) -> bool,
+    {
+        self.check_all_shards_locked();
+
+        // removed nodes are free'd once out of the read-side critical section, even if `f` panics
+        let mut removed = UrcuReclaimGuard::new(self._thread.ht.reclaim_mode);
+
+        unsafe {
+            // RCU read-side lock must be held while walking the hashtable.
+            let rdlock = flavor::ReadLock::new();
+
+            let mut iter = RcuHtIter::<K, V>::new(self.urcuht);
+            loop {
+                let found_node = iter.next_node();
+                if found_node.is_null() {
+                    break;
+                }
+
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                if !f(&*node) {
+                    // Only a single writer can delete nodes, so this should never fail.
+                    if urcu_delete_node(self.urcuht, found_node) == 0 {
+                        removed.nodes.push(node);
+                        self._thread.ht.notify_remove(node);
+                    }
+                }
+            }
+
+            drop(rdlock);
+        }
+    }
+
+    /// Resize the bucket array to fit the current number of objects.
+    ///
+    /// The new size is the node count rounded up to a power of two, never below the minimum number
+    /// of allocated buckets (nor above the maximum) requested at creation.
+    ///
+    /// This function waits for a grace period: it must not be called while a read lock is held by
+    /// this thread.
+    pub fn shrink_to_fit(&mut self) {
+        self.check_all_shards_locked();
+
+        unsafe {
+            flavor::read_lock();
+            let count = urcu_count_nodes(self.urcuht);
+            flavor::read_unlock();
+
+            self.resize_for(count);
+        }
+    }
+
+    /// Resize the bucket array up front, so that `additional` more objects fit in it.
+    ///
+    /// As for [`shrink_to_fit`](Self::shrink_to_fit), the new size is rounded up to a power of two,
+    /// within the bucket limits requested at creation. The bucket array never shrinks here. This is
+    /// mostly useful before a bulk load into a hashtable without automatic resize.
+    ///
+    /// This function waits for a grace period: it must not be called while a read lock is held by
+    /// this thread.
+    pub fn reserve(&mut self, additional: usize) {
+        self.check_all_shards_locked();
+
+        unsafe {
+            flavor::read_lock();
+            let count = urcu_count_nodes(self.urcuht);
+            flavor::read_unlock();
+
+            let needed = count.saturating_add(additional as u64);
+            if needed > self._thread.ht.bucket_count.load(Ordering::Relaxed) {
+                self.resize_for(needed);
+            }
+        }
+    }
+
+    /// Resize the bucket array to fit `count` objects, within the bucket limits of the hashtable.
+    unsafe fn resize_for(&mut self, count: u64) {
+        let ht = self._thread.ht;
+
+        let mut new_size = nearest_power_of_two(count.max(1)).max(ht.min_nr_alloc_buckets);
+        if ht.max_nr_buckets != 0 {
+            new_size = new_size.min(ht.max_nr_buckets);
+        }
+
+        urcu_sys::cds_lfht_resize(self.urcuht, new_size);
+
+        ht.bucket_count.store(new_size, Ordering::Relaxed);
+    }
+
+    /// Wait until all objects removed or replaced so far are actually free'd.
+    ///
+    /// Unlike a grace period (synchronize_rcu), this waits for all queued delayed free callbacks
+    /// (call_rcu) to complete.
+    ///
+    /// This function must not be called while a read lock is held by this thread.
+    pub fn flush_reclamation(&mut self) {
+        flavor::barrier();
+    }
+
+    /// Wait for a grace period (synchronize_rcu): all readers which could access objects removed
+    /// so far are done. It does not wait for the delayed free callbacks themselves.
+    ///
+    /// This function must not be called while a read lock is held by this thread.
+    pub fn force_grace_period(&mut self) {
+        flavor::synchronize();
+    }
+
+    /// Push all pending reclamation through, to make the hashtable as clean as possible now.
+    ///
+    /// It waits for a grace period, then for all queued delayed free callbacks: once it returns,
+    /// all objects removed or replaced so far are free'd. Useful at a quiet point of a program.
+    ///
+    /// This function must not be called while a read lock is held by this thread.
+    pub fn gc(&mut self) {
+        self.force_grace_period();
+        self.flush_reclamation();
+    }
+
+    /// Get a reference to the value indexed by `key`, from the writer.
+    ///
+    /// Nodes are only freed by the writer holding the write mutex (of the key shard), so the
+    /// returned reference stays valid as long as this writer is borrowed (no insert or remove can
+    /// happen meanwhile).
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let h = self.locked_key_hash(key);
+
+        let mut ret: Option<&V> = None;
+
+        unsafe {
+            flavor::read_lock();
+
+            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, h, key);
+
+            if !found_node.is_null() {
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                ret = Some(&(*node).data);
+            }
+
+            flavor::read_unlock();
+        }
+
+        urcu_metrics_record_lookup(ret)
+    }
+
+    /// Get a reference to the value indexed by `key`, inserting the value computed by `f` first
+    /// if the key is not in hashtable.
+    ///
+    /// `f` is only called on a miss. If it fails, nothing is inserted, `key` is dropped and the
+    /// error is returned. As for [`get`](Self::get), the reference stays valid as long as this
+    /// writer is borrowed.
+    pub fn get_or_try_insert_with<F, E>(&mut self, key: K, f: F) -> Result<&V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        let h = self.locked_key_hash(&key);
+        let ht = self._thread.ht;
+
+        unsafe {
+            flavor::read_lock();
+            let found_node = urcu_get_node::<K, K, V>(self.urcuht, h, &key);
+            flavor::read_unlock();
+
+            if !found_node.is_null() {
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                return Ok(&(*node).data);
+            }
+
+            let val = ht.alloc_node(key, f()?);
+
+            // There is no concurrent writer for this key: it cannot be inserted meanwhile.
+            let old_node = urcu_add_replace_node(self.urcuht, h, val);
+            debug_assert!(old_node.is_null());
+            ht.notify_insert(val);
+
+            // The new object is the newest one: it is never evicted (capacity is at least 1).
+            ht.evict_over_capacity();
+
+            Ok(&(*val).data)
+        }
+    }
+
+    /// Get a reference to the value indexed by `key`, inserting `V::default()` first if the key
+    /// is not in hashtable. See [`get_or_try_insert_with`](Self::get_or_try_insert_with).
+    pub fn get_or_default(&mut self, key: K) -> &V
+    where
+        V: Default,
+    {
+        match self.get_or_try_insert_with(key, || Ok::<V, std::convert::Infallible>(V::default())) {
+            Ok(value) => value,
+            Err(err) => match err {},
+        }
+    }
+
+    /// Compute a new value for `key` from its current one (None if the key is not in hashtable).
+    ///
+    /// If `f` returns a value, it is inserted (or replaces the current one, copy-on-write).
+    /// If it returns None, the current object (if any) is removed. An owned key is only built
+    /// (with `K::from`) when a value is stored.
+    pub fn compute<'q, Q, F>(&mut self, key: &'q Q, f: F)
+    where
+        K: Borrow<Q> + From<&'q Q>,
+        Q: ?Sized + Hash + Eq,
+        F: FnOnce(Option<&V>) -> Option<V>,
+    {
+        let current = self.get(key);
+        let found = current.is_some();
+
+        match f(current) {
+            Some(value) => self.insert_or_replace(K::from(key), value),
+            None if found => {
+                let _ = self.remove(key);
+            }
+            None => {}
+        }
+    }
+
+    /// Add `delta` to the integer value indexed by `key` (0 if the key is not in hashtable), and
+    /// return the new value. The object is replaced by a new one (copy-on-write).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new value overflows `i64` or does not fit in `V`.
+    pub fn increment(&mut self, key: K, delta: i64) -> i64
+    where
+        V: Copy + Into<i64> + TryFrom<i64>,
+    {
+        let current: i64 = self.get(&key).map_or(0, |value| (*value).into());
+        let new = current
+            .checked_add(delta)
+            .expect("Counter overflow in RcuHtWriter::increment");
+        let value = V::try_from(new)
+            .ok()
+            .expect("Counter value out of range in RcuHtWriter::increment");
+        self.insert_or_replace(key, value);
+        new
+    }
+
+    /// Get the entry of a borrowed `key`, to insert an object only if the key is not in hashtable.
+    ///
+    /// An owned key is only built (with `K::from`) when a vacant entry is filled, so probing with
+    /// an expensive key does not clone it.
+    pub fn entry_ref<'w, 'q, Q>(
+        &'w mut self,
+        key: &'q Q,
+    ) -> EntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let hash = self.locked_key_hash(key);
+
+        unsafe {
+            flavor::read_lock();
+            let found_node = urcu_get_node::<Q, K, V>(self.urcuht, hash, key);
+            flavor::read_unlock();
+
+            if !found_node.is_null() {
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+                return EntryRef::Occupied(&(*node).data);
+            }
+        }
+
+        EntryRef::Vacant(VacantEntryRef {
+            writer: self,
+            key,
+            hash,
+        })
+    }
+
+    /// Replace the value indexed by `key` with `new`, only if current value equals `expected`.
+    ///
+    /// Returns true if the value was replaced, false if current value is different. With duplicate
+    /// keys, the first object whose value equals `expected` is replaced.
+    /// This function fails with NotFound if the key is not in hashtable.
+    /// Stored key is cloned since the new value needs a new node.
+    pub fn compare_and_swap<Q>(&mut self, key: &Q, expected: &V, new: V) -> Result<bool, RcuError>
+    where
+        K: Borrow<Q> + Clone,
+        Q: ?Sized + Hash + Eq,
+        V: PartialEq,
+    {
+        let h = self.locked_key_hash(key);
+        let ht = self._thread.ht;
+
+        // the replaced node is free'd once out of the read-side critical section
+        let mut replaced = UrcuReclaimGuard::new(ht.reclaim_mode);
+        let mut found = false;
+
+        unsafe {
+            // released even if PartialEq (or Clone) of user types panics
+            let rdlock = flavor::ReadLock::new();
+
+            // with duplicate keys, the first object matching `expected` is replaced
+            let mut iter = urcu_lookup::<Q, K, V>(self.urcuht, h, key);
+            while !iter.node.is_null() {
+                found = true;
+
+                let node = urcu_cds_lfht_node_to_rust_type::<K, V>(iter.node);
+                if (*node).data == *expected {
+                    let val = ht.alloc_node((*node).key.clone(), new);
+
+                    // There is no concurrent writer for this key: it cannot be removed meanwhile.
+                    let old_node = urcu_replace_at(self.urcuht, &mut iter, val);
+                    debug_assert_eq!(old_node, node);
+                    ht.notify_insert(val);
+                    replaced.nodes.push(old_node);
+                    break;
+                }
+
+                urcu_next_duplicate::<Q, K, V>(self.urcuht, key, &mut iter);
+            }
+
+            drop(rdlock);
+        }
+
+        if !replaced.nodes.is_empty() {
+            Ok(true)
+        } else if found {
+            Ok(false)
+        } else {
+            Err(RcuError::NotFound)
+        }
+    }
+
+    /// Apply a single operation (`fuzzing` feature only).
+    ///
+    /// Returns the same value as the matching `std::collections::HashMap` operation: the
+    /// replaced value for [`Op::Insert`], the removed one for [`Op::Remove`], the found one for
+    /// [`Op::Get`], None otherwise.
+    #[cfg(feature = "fuzzing")]
+    pub fn apply_op(&mut self, op: Op<K, V>) -> Option<V>
+    where
+        V: Clone,
+    {
+        match op {
+            Op::Insert(key, value) => self.swap(key, value),
+            Op::Remove(key) => {
+                let value = self.get(&key).cloned();
+                if value.is_some() {
+                    self.remove(&key).expect("Cannot remove an existing key");
+                }
+                value
+            }
+            Op::Get(key) => self.get(&key).cloned(),
+            Op::Clear => {
+                self.clear();
+                None
+            }
+            Op::FlushReclamation => {
+                self.flush_reclamation();
+                None
+            }
+        }
+    }
+
+    /// Remove all objects from the hashtable and return them.
+    ///
+    /// All nodes are deleted first, then we wait for a single grace period (synchronize_rcu)
+    /// before moving keys and values out of deleted nodes.
+    /// This thread must not hold a read lock while calling this function, it would never return.
+    pub fn drain(&mut self) -> Vec<(K, V)> {
+        self.check_all_shards_locked();
+
+        let drained = unsafe { urcu_drain(self.urcuht) };
+        for (key, _) in &drained {
+            self._thread.ht.notify_change(&ChangeEvent::Remove { key });
+        }
+
+        drained
+    }
+
+    /// Remove an arbitrary object from the hashtable and return it, None if it is empty.
+    ///
+    /// This lets the hashtable be consumed as a work queue. As for [`drain`](Self::drain), we wait
+    /// for a grace period before moving key and value out of the deleted node: this thread must
+    /// not hold a read lock while calling this function, it would never return.
+    pub fn pop_any(&mut self) -> Option<(K, V)> {
+        self.check_all_shards_locked();
+
+        unsafe {
+            flavor::read_lock();
+            let found_node = RcuHtIter::<K, V>::new(self.urcuht).next_node();
+            // Only a single writer can delete nodes, so this should never fail.
+            let deleted =
+                !found_node.is_null() && urcu_sys::cds_lfht_del(self.urcuht, found_node) == 0;
+            flavor::read_unlock();
+
+            if !deleted {
+                return None;
+            }
+
+            let node = urcu_cds_lfht_node_to_rust_type::<K, V>(found_node);
+            self._thread.ht.notify_remove(node);
+
+            // Wait for all readers which could still access the deleted node
+            flavor::synchronize();
+
+            Some(urcu_take_node(node))
+        }
+    }
+}
+
+/// Entry of a borrowed key, see [`RcuHtWriter::entry_ref`].
+pub enum EntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q: ?Sized, S = WyHashBuilder> {
+    /// The key is in hashtable: reference to its value, valid as long as the writer is borrowed.
+    Occupied(&'w V),
+    /// The key is not in hashtable.
+    Vacant(VacantEntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S>),
+}
+
+impl<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S> EntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S>
+where
+    K: Hash + Eq + From<&'q Q>,
+    Q: ?Sized,
+    S: BuildHasher,
+{
+    /// Get a reference to the value, inserting `default` first if the entry is vacant.
+    pub fn or_insert(self, default: V) -> &'w V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Get a reference to the value, inserting the value computed by `f` first if the entry is
+    /// vacant. `f` is only called on a vacant entry.
+    pub fn or_insert_with<F>(self, f: F) -> &'w V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            EntryRef::Occupied(value) => value,
+            EntryRef::Vacant(entry) => entry.insert(f()),
+        }
+    }
+}
+
+/// Vacant entry of a borrowed key, see [`RcuHtWriter::entry_ref`].
+pub struct VacantEntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q: ?Sized, S = WyHashBuilder> {
+    writer: &'w mut RcuHtWriter<'guard, 'thread, 'ht, K, V, S>,
+    key: &'q Q,
+    hash: u64,
+}
+
+impl<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S>
+    VacantEntryRef<'w, 'guard, 'thread, 'ht, 'q, K, V, Q, S>
+where
+    K: Hash + Eq + From<&'q Q>,
+    Q: ?Sized,
+    S: BuildHasher,
+{
+    /// Insert `value` with an owned key built from the borrowed one, and get a reference to it.
+    pub fn insert(self, value: V) -> &'w V {
+        let ht = self.writer._thread.ht;
+
+        unsafe {
+            let val = ht.alloc_node(K::from(self.key), value);
+
+            // There is no concurrent writer for this key: it cannot be inserted meanwhile.
+            let old_node = urcu_add_replace_node(self.writer.urcuht, self.hash, val);
+            debug_assert!(old_node.is_null());
+            ht.notify_insert(val);
+
+            // The new object is the newest one: it is never evicted (capacity is at least 1).
+            ht.evict_over_capacity();
+
+            &(*val).data
+        }
+    }
+}
+
+/// Writer object used to perform add and del operations concurrently with other writers.
+///
+/// It is only available for hashtables built with [`RcuHtBuilder::lock_free_writes`].
+/// Objects can be replaced or removed by other writers at any time, so this writer does not
+/// provide any lookup: use a read lock instead.
+/// It must not be shared between threads.
+pub struct RcuHtConcurrentWriter<'thread, 'ht, K, V, S = WyHashBuilder> {
+    urcuht: *mut urcu_sys::cds_lfht,
+    thread: &'thread RcuHtThread<'ht, K, V, S>,
+}
+
+impl<'thread, 'ht, K, V, S> RcuHtConcurrentWriter<'thread, 'ht, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Add or replace an existing key/value.
+    ///
+    /// When concurrent writers replace the same key, each replaced object is free'd exactly once,
+    /// after a grace period.
+    pub fn insert_or_replace(&self, key: K, value: V) {
+        let h = self.thread.ht.key_hash(&key);
+
+        unsafe {
+            let val = self.thread.ht.alloc_node(key, value);
+            // another writer may replace it as soon as it is published: report it before
+            self.thread.ht.notify_insert(val);
+            urcu_replace_node(self.urcuht, h, val, self.thread.ht.reclaim_mode);
+        }
+    }
+
+    /// Add or replace an existing key/value, without aborting on allocation failure.
+    pub fn try_insert_or_replace(&self, key: K, value: V) -> Result<(), (K, V)> {
+        let h = self.thread.ht.key_hash(&key);
+
+        unsafe {
+            let val = self.thread.ht.try_alloc_node(key, value)?;
+            // another writer may replace it as soon as it is published: report it before
+            self.thread.ht.notify_insert(val);
+            urcu_replace_node(self.urcuht, h, val, self.thread.ht.reclaim_mode);
+        }
+
+        Ok(())
+    }
+
+    /// Add a new key/value, only if the key is not already in hashtable.
+    ///
+    /// On conflict, existing object is left untouched and `value` is given back to the caller.
+    pub fn insert_if_absent(&self, key: K, value: V) -> Result<(), V> {
+        let h = self.thread.ht.key_hash(&key);
+
+        unsafe {
+            let val = self.thread.ht.alloc_node(key, value);
+
+            // another writer may remove it once published: it is not free'd until this read lock
+            // is released.
+            let rdlock = flavor::ReadLock::new();
+            urcu_insert_unique(self.urcuht, h, val)?;
+            self.thread.ht.notify_insert(val);
+            drop(rdlock);
+        }
+
+        Ok(())
+    }
+
+    /// Delete the value indexed by the `key` from the hashtable.
+    ///
+    /// This function fails with NotFound if node is not found, or if it is removed (or replaced)
+    /// by another writer at the same time.
+    pub fn remove<Q>(&self, key: &Q) -> Result<(), RcuError>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let h = self.thread.ht.key_hash(key);
+
+        match unsafe {
+            urcu_remove::<Q, K, V>(
+                self.urcuht,
+                h,
+                key,
+                false,
+                self.thread.ht.reclaim_mode,
+                |key| self.thread.ht.notify_change(&ChangeEvent::Remove { key }),
+            )
+        } {
+            // someone else removed it between our lookup and removal
+            Err(RcuError::DeleteError(_)) => Err(RcuError::NotFound),
+            ret => ret,
+        }
+    }
+
+    /// Wait until all objects removed or replaced so far (by any writer) are actually free'd.
+    ///
+    /// This function must not be called while a read lock is held by this thread.
+    pub fn flush_reclamation(&self) {
+        flavor::barrier();
+    }
+}
+
+/// A consistent copy of a hashtable, see [`RcuHt::snapshot`].
+///
+/// It derefs to a std HashMap, which is not updated by later writes.
+#[derive(Debug, Clone)]
+pub struct RcuHtSnapshot<K, V>(std::collections::HashMap<K, V>);
+
+impl<K, V> RcuHtSnapshot<K, V> {
+    /// Get the copied objects.
+    pub fn into_inner(self) -> std::collections::HashMap<K, V> {
+        self.0
+    }
+}
+
+impl<K, V> std::ops::Deref for RcuHtSnapshot<K, V> {
+    type Target = std::collections::HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// A set of shared keys, so that identical keys inserted again and again share a single
+/// allocation.
+///
+/// It is meant for `RcuHt<Arc<str>, V>` (or `Arc<[u8]>`...) tables whose keys come from a small
+/// set: interning a key only clones an `Arc` once the key is known, instead of allocating a new
+/// key for each insert. Lookups still take a borrowed key (`&str`). Interned keys are kept until
+/// [`purge`](Self::purge) is called.
+///
+/// ```
+/// use std::sync::Arc;
+/// use urcu_ht::{KeyInterner, RcuHt};
+///
+/// let ht = RcuHt::<Arc<str>, u32>::new(64, 64, 64, false).unwrap();
+/// let keys = KeyInterner::<str>::new();
+///
+/// let thread = ht.thread();
+/// thread.wrlock().unwrap().insert_or_replace(keys.intern("session"), 1);
+/// assert_eq!(thread.rdlock().get("session"), Some(&1));
+/// ```
+#[derive(Debug)]
+pub struct KeyInterner<T: ?Sized>(Mutex<std::collections::HashSet<std::sync::Arc<T>>>);
+
+impl<T: ?Sized + Hash + Eq> KeyInterner<T> {
+    pub fn new() -> Self {
+        KeyInterner(Mutex::new(std::collections::HashSet::new()))
+    }
+
+    /// Get the shared copy of `key`, allocating it only if it was not interned yet.
+    pub fn intern<'a>(&self, key: &'a T) -> std::sync::Arc<T>
+    where
+        std::sync::Arc<T>: From<&'a T>,
+    {
+        let mut keys = self.0.lock().unwrap_or_else(|err| err.into_inner());
+
+        match keys.get(key) {
+            Some(shared) => shared.clone(),
+            None => {
+                let shared = std::sync::Arc::from(key);
+                keys.insert(shared.clone());
+                shared
+            }
+        }
+    }
+
+    /// Number of interned keys.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget keys which are not used anymore (only referenced by this interner).
+    ///
+    /// Keys of objects removed from a hashtable are only released once these objects are
+    /// free'd, after a grace period.
+    pub fn purge(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .retain(|shared| std::sync::Arc::strong_count(shared) > 1);
+    }
+}
+
+impl<T: ?Sized + Hash + Eq> Default for KeyInterner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A hashtable of String keys and values, with `&str` friendly methods.
+///
+/// Each method gets its own thread handle and lock, and values are copied out of the read lock.
+/// The generic API of [`RcuHt`] is still available (it derefs to it) for anything else.
+pub struct RcuStringMap<S = WyHashBuilder>(RcuHt<String, String, S>);
+
+impl RcuStringMap {
+    /// Allocate a new hashtable, see [`RcuHt::new`].
+    pub fn new(
+        init_size: u64,
+        min_nr_alloc_buckets: u64,
+        max_nr_buckets: u64,
+        autoresize: bool,
+    ) -> Result<Self, RcuError> {
+        RcuHt::new(init_size, min_nr_alloc_buckets, max_nr_buckets, autoresize).map(RcuStringMap)
+    }
+}
+
+impl<S> RcuStringMap<S>
+where
+    S: BuildHasher,
+{
+    /// Get a copy of the value indexed by `key`.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let thread = self.0.thread();
+        let rdlock = thread.rdlock();
+        rdlock.get(key).cloned()
+    }
+
+    /// Returns true if `key` is in hashtable.
+    pub fn contains_key(&self, key: &str) -> bool {
+        let thread = self.0.thread();
+        let rdlock = thread.rdlock();
+        rdlock.get(key).is_some()
+    }
+
+    /// Insert or replace an object, returning a copy of the replaced value.
+    ///
+    /// The replaced value is free'd after a grace period, which is not waited for.
+    /// This function fails with LockPoisoned if a writer panicked.
+    pub fn insert(&self, key: String, value: String) -> Result<Option<String>, RcuError> {
+        let thread = self.0.thread();
+        let mut wrlock = thread.wrlock().ok_or(RcuError::LockPoisoned)?;
+        // there is no concurrent writer: the copied value is the one replaced
+        let replaced = wrlock.get(&key).cloned();
+        wrlock.insert_or_replace(key, value);
+        Ok(replaced)
+    }
+
+    /// Delete the value indexed by `key`. This function may fail if key is not found, or with
+    /// LockPoisoned if a writer panicked.
+    pub fn remove(&self, key: &str) -> Result<(), RcuError> {
+        let thread = self.0.thread();
+        let mut wrlock = thread.wrlock().ok_or(RcuError::LockPoisoned)?;
+        wrlock.remove(key)
+    }
+}
+
+impl<S> std::ops::Deref for RcuStringMap<S> {
+    type Target = RcuHt<String, String, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RcuError, RcuHt, RcuHtBuilder, ReclaimMode};
+
+    #[test]
+    fn it_works() {
+        // Type inference lets us omit an explicit type signature (which
+        // would be `HashMap<String, String>` in this example).
+        let ht = RcuHt::<String, String>::new(64, 64, 64, false)
+            .expect("Cannot create hashtable, probably due to invalid parameters");
+        let ht = std::sync::Arc::new(ht);
+        // Create a new thread to get book reviews.
+        let child = {
+            let ht = ht.clone();
+            std::thread::spawn(move || {
+                {
+                    // Get a read handle for this thread
+                    let ht = ht.thread();
+                    // wait until main thread adds some reviews
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+
+                    let rdlock = ht.rdlock();
+
+                    let review = rdlock.get("Adventures of Huckleberry Finn");
+                    assert!(review.is_some());
+                    let review = review.unwrap();
+                    assert!(review.eq("My favorite book."));
+                }
+
+                // uncomment this to check rdlock cannot live longer than ht thread
+                /*
+                let _out_error = {
+                    // Get a read handle for this thread
+                    let ht = ht.thread();
+
+                    // get a rdlock and return it
+                    ht.rdlock()
+                };
+                */
+
+                // uncommend this to check wrlock cannot live longer than ht thread
+                /*
+                let _out_error = {
+                    // Get a read handle for this thread
+                    let ht = ht.thread();
+
+                    // get a rdlock and return it
+                    ht.wrlock()
+                };
+                */
+
+                // uncomment this to check a ref to a value cannot live longer than rdlock
+                /*
+                // Get a read handle for this thread
+                let ht = ht.thread();
+
+                let _out_error = {
+                    // get a rdlock
+                    let rdlock = ht.rdlock();
+
+                    // get a reference to a value then try to return it will lock is released
+                    rdlock.get("Adventures of Huckleberry Finn")
+                };
+                */
+            })
+        };
+
+        let ht = ht.thread();
+        {
+            let mut wrlock = ht.wrlock().unwrap();
+            wrlock.insert_or_replace(
+                "Adventures of Huckleberry Finn".to_string(),
+                "My favorite book.".to_string(),
+            );
+        }
+
+        {
+            let mut wrlock = ht.wrlock().unwrap();
+            wrlock.insert_or_replace(
+                "Grimms' Fairy Tales".to_string(),
+                "Masterpiece.".to_string(),
+            );
+        }
+
+        child.join().expect("cannot join thread");
 
         // uncommend this to check RcuHtThread cannot live longer than RcuHt
         /*
@@ -693,9 +4390,2868 @@ mod tests {
             // Get a read handle for this thread
             let thread = ht.thread();
 
-            // get a rdlock and return it
-            thread
+            // get a rdlock and return it
+            thread
+        };
+        */
+    }
+
+    #[test]
+    fn get_many() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace("one".to_string(), 1);
+            wrlock.insert_or_replace("two".to_string(), 2);
+        }
+
+        let rdlock = thread.rdlock();
+        let values = rdlock.get_many(&["two", "zero", "one", "two"]);
+        assert_eq!(values, vec![Some(&2), None, Some(&1), Some(&2)]);
+        assert!(rdlock.get_many::<str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn load_factor() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        assert_eq!(thread.rdlock().load_factor(), 0.0);
+
+        let mut previous = 0.0;
+        for i in 0..4 {
+            {
+                let mut wrlock = thread.wrlock().unwrap();
+                for j in 0..32 {
+                    wrlock.insert_or_replace(i * 32 + j, 0);
+                }
+            }
+
+            let load_factor = thread.rdlock().load_factor();
+            assert!(load_factor > previous);
+            previous = load_factor;
+        }
+
+        assert_eq!(previous, 2.0);
+    }
+
+    #[test]
+    fn count_where() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        assert_eq!(thread.rdlock().count_where(|_, _| true), 0);
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.count_where(|_, v| v % 2 == 0), 50);
+        assert_eq!(rdlock.count_where(|k, _| *k < 10), 10);
+    }
+
+    #[test]
+    fn first() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        assert!(thread.rdlock().first().is_none());
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace(1, 10);
+            wrlock.insert_or_replace(2, 20);
+        }
+
+        let rdlock = thread.rdlock();
+        let (key, value) = rdlock.first().unwrap();
+        assert_eq!(*key * 10, *value);
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.insert_or_replace("state".to_string(), 1);
+
+        assert!(wrlock.compare_and_swap("state", &1, 2).unwrap());
+        assert!(!wrlock.compare_and_swap("state", &1, 3).unwrap());
+        assert!(matches!(
+            wrlock.compare_and_swap("missing", &1, 3),
+            Err(RcuError::NotFound)
+        ));
+        drop(wrlock);
+
+        assert_eq!(thread.rdlock().get("state"), Some(&2));
+    }
+
+    /// Value type counting how many times it is dropped.
+    #[derive(Debug)]
+    struct DropCounter(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn external_registration() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let count = || super::URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.get());
+
+                crate::register_current_thread();
+                {
+                    let thread = ht.thread();
+                    assert_eq!(count(), 0);
+                    thread.wrlock().unwrap().insert_or_replace(1, 1);
+                    assert_eq!(thread.rdlock().get(&1), Some(&1));
+                }
+                assert_eq!(count(), 0);
+                crate::unregister_current_thread();
+
+                // back to automatic registration
+                let thread = ht.thread();
+                assert_eq!(count(), 1);
+                drop(thread);
+                assert_eq!(count(), 0);
+            });
+        });
+    }
+
+    #[test]
+    fn into_iter() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i * 2);
+            }
+        }
+
+        let mut pairs: Vec<(u32, u32)> = ht.into_iter().collect();
+        pairs.sort();
+        assert_eq!(pairs, (0..100).map(|i| (i, i * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        for (init, min, max) in [
+            (0, 64, 64),
+            (48, 64, 64),
+            (64, 0, 64),
+            (64, 3, 64),
+            (64, 64, 100),
+            (64, 128, 64),
+            (128, 64, 64),
+        ] {
+            match RcuHt::<u32, u32>::new(init, min, max, false) {
+                Err(RcuError::InvalidSize(_)) => (),
+                _ => panic!("{} {} {} must be rejected", init, min, max),
+            }
+        }
+
+        assert!(RcuHt::<u32, u32>::new(64, 64, 0, false).is_ok());
+
+        assert!(matches!(
+            RcuHt::<u32, u32>::new(128, 64, 64, false),
+            Err(RcuError::InvalidSize(
+                "init_size must not be greater than max_nr_buckets"
+            ))
+        ));
+    }
+
+    #[test]
+    fn parameters() {
+        let ht = RcuHt::<u32, u32>::new(32, 16, 128, true).unwrap();
+        assert_eq!(ht.init_size(), 32);
+        assert_eq!(ht.min_buckets(), 16);
+        assert_eq!(ht.max_buckets(), 128);
+        assert!(ht.autoresize_enabled());
+
+        let ht = RcuHt::<u32, u32>::new(64, 64, 0, false).unwrap();
+        assert_eq!(ht.max_buckets(), 0);
+        assert!(!ht.autoresize_enabled());
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let ht = RcuHt::<u32, u32>::new(1024, 16, 0, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..1000 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        for i in 10..1000 {
+            wrlock.remove(&i).unwrap();
+        }
+
+        wrlock.shrink_to_fit();
+
+        for i in 0..10 {
+            assert_eq!(wrlock.get(&i), Some(&i));
+        }
+        assert_eq!(wrlock.get(&10), None);
+        drop(wrlock);
+
+        // 10 objects, but never below the 16 buckets minimum
+        assert_eq!(thread.rdlock().load_factor(), 10.0 / 16.0);
+    }
+
+    #[test]
+    fn try_insert_or_replace() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        assert_eq!(wrlock.try_insert_or_replace(1, 10), Ok(()));
+        assert_eq!(wrlock.try_insert_or_replace(1, 11), Ok(()));
+        assert_eq!(wrlock.get(&1), Some(&11));
+    }
+
+    #[test]
+    fn writer_get() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.insert_or_replace(1, 10);
+
+        // increment existing value, insert missing one
+        for key in [1, 2] {
+            let next = match wrlock.get(&key) {
+                Some(value) => value + 1,
+                None => 0,
+            };
+            wrlock.insert_or_replace(key, next);
+        }
+
+        assert_eq!(wrlock.get(&1), Some(&11));
+        assert_eq!(wrlock.get(&2), Some(&0));
+        assert_eq!(wrlock.get(&3), None);
+    }
+
+    #[test]
+    fn error_display() {
+        use crate::DeleteErrorKind;
+
+        assert_eq!(
+            DeleteErrorKind::from_code(-libc::ENOENT),
+            DeleteErrorKind::AlreadyRemoved
+        );
+        assert_eq!(
+            DeleteErrorKind::from_code(-libc::EINVAL),
+            DeleteErrorKind::Other(-libc::EINVAL)
+        );
+
+        let errors = [
+            (RcuError::InvalidParameters, "invalid parameters"),
+            (
+                RcuError::InvalidSize("init_size must be a power of two"),
+                "invalid hashtable size: init_size must be a power of two",
+            ),
+            (RcuError::NotFound, "object not found in hashtable"),
+            (
+                RcuError::DeleteError(DeleteErrorKind::AlreadyRemoved),
+                "cannot delete object: object already removed",
+            ),
+            (
+                RcuError::DeleteError(DeleteErrorKind::Other(-1)),
+                "cannot delete object: urcu lib error code -1",
+            ),
+        ];
+
+        for (error, msg) in errors {
+            let error: &dyn std::error::Error = &error;
+            assert_eq!(error.to_string(), msg);
+        }
+    }
+
+    #[test]
+    fn boxed_error() {
+        fn remove_missing() -> Result<(), Box<dyn std::error::Error>> {
+            let ht = RcuHt::<u32, u32>::new(64, 64, 64, false)?;
+            let thread = ht.thread();
+            thread.wrlock().unwrap().remove(&1)?;
+            Ok(())
+        }
+
+        let error = remove_missing().unwrap_err();
+        assert_eq!(error.to_string(), "object not found in hashtable");
+        assert!(matches!(
+            error.downcast_ref::<RcuError>(),
+            Some(RcuError::NotFound)
+        ));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn wrlock_async() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        let mut wrlock = thread.wrlock_async().await.unwrap();
+        wrlock.insert_or_replace(1, 1);
+        drop(wrlock);
+        assert_eq!(thread.rdlock().get(&1), Some(&1));
+
+        // the async writer sleeps until another writer of this executor thread releases its lock
+        let released = std::cell::Cell::new(false);
+        let holder = async {
+            let wrlock = thread.wrlock().unwrap();
+            tokio::task::yield_now().await;
+            released.set(true);
+            drop(wrlock);
+        };
+        let waiter = async {
+            let mut wrlock = thread.wrlock_async().await.unwrap();
+            assert!(released.get());
+            wrlock.insert_or_replace(2, 2);
+        };
+        tokio::join!(holder, waiter);
+        assert_eq!(thread.rdlock().get(&2), Some(&2));
+    }
+
+    #[test]
+    fn nearest_power_of_two() {
+        assert_eq!(crate::nearest_power_of_two(0), 1);
+        assert_eq!(crate::nearest_power_of_two(1), 1);
+        assert_eq!(crate::nearest_power_of_two(3), 4);
+        assert_eq!(crate::nearest_power_of_two(1000), 1024);
+        assert_eq!(crate::nearest_power_of_two(u64::MAX), 1 << 63);
+
+        let ht = RcuHt::<u32, u32>::new(crate::nearest_power_of_two(100), 16, 0, false).unwrap();
+        assert_eq!(ht.thread().rdlock().bucket_count(), 128);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        /// Recorder keeping one counter per "op" label.
+        #[derive(Default)]
+        struct TestRecorder(Mutex<std::collections::HashMap<String, Arc<AtomicU64>>>);
+
+        impl TestRecorder {
+            fn get(&self, op: &str) -> u64 {
+                match self.0.lock().unwrap().get(op) {
+                    Some(counter) => counter.load(Ordering::SeqCst),
+                    None => 0,
+                }
+            }
+        }
+
+        impl metrics::Recorder for TestRecorder {
+            fn describe_counter(
+                &self,
+                _: metrics::KeyName,
+                _: Option<metrics::Unit>,
+                _: metrics::SharedString,
+            ) {
+            }
+            fn describe_gauge(
+                &self,
+                _: metrics::KeyName,
+                _: Option<metrics::Unit>,
+                _: metrics::SharedString,
+            ) {
+            }
+            fn describe_histogram(
+                &self,
+                _: metrics::KeyName,
+                _: Option<metrics::Unit>,
+                _: metrics::SharedString,
+            ) {
+            }
+
+            fn register_counter(
+                &self,
+                key: &metrics::Key,
+                _: &metrics::Metadata<'_>,
+            ) -> metrics::Counter {
+                assert_eq!(key.name(), "urcu_ht_operations");
+                let op = key.labels().find(|label| label.key() == "op").unwrap();
+                let counter = self
+                    .0
+                    .lock()
+                    .unwrap()
+                    .entry(op.value().to_string())
+                    .or_default()
+                    .clone();
+                metrics::Counter::from_arc(counter)
+            }
+
+            fn register_gauge(
+                &self,
+                _: &metrics::Key,
+                _: &metrics::Metadata<'_>,
+            ) -> metrics::Gauge {
+                metrics::Gauge::noop()
+            }
+
+            fn register_histogram(
+                &self,
+                _: &metrics::Key,
+                _: &metrics::Metadata<'_>,
+            ) -> metrics::Histogram {
+                metrics::Histogram::noop()
+            }
+        }
+
+        let recorder = TestRecorder::default();
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace(1, 1);
+            wrlock.insert_or_replace(2, 2);
+            wrlock.remove(&2).unwrap();
+            assert!(wrlock.remove(&3).is_err());
+            // every write path is recorded, failed writes are not
+            assert!(wrlock.insert_if_absent(3, 3).is_ok());
+            assert!(wrlock.insert_if_absent(3, 3).is_err());
+            wrlock.update_all(|_, value| Some(value + 1));
+            assert!(wrlock.remove_if(&3, |_| true).unwrap());
+            wrlock.insert_or_replace(4, 4);
+            wrlock.retain(|key, _| *key != 4);
+            drop(wrlock);
+
+            let rdlock = thread.rdlock();
+            assert!(rdlock.get(&1).is_some());
+            assert!(rdlock.get(&2).is_none());
+        });
+
+        assert_eq!(recorder.get("insert"), 6);
+        assert_eq!(recorder.get("remove"), 3);
+        assert_eq!(recorder.get("lookup_hit"), 1);
+        assert_eq!(recorder.get("lookup_miss"), 1);
+        // deferred frees are recorded from urcu lib's call_rcu thread (global recorder only)
+    }
+
+    #[test]
+    fn to_hashmap() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut expected = std::collections::HashMap::new();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i.to_string());
+            expected.insert(i, i.to_string());
+        }
+        drop(wrlock);
+
+        assert_eq!(thread.rdlock().to_hashmap(), expected);
+    }
+
+    #[test]
+    fn contains_all_any() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace("a".to_string(), 1);
+        wrlock.insert_or_replace("b".to_string(), 2);
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        assert!(rdlock.contains_all::<str>(&["a", "b"]));
+        assert!(!rdlock.contains_all::<str>(&["a", "c"]));
+        assert!(rdlock.contains_all::<str>(&[]));
+        assert!(rdlock.contains_any::<str>(&["c", "b"]));
+        assert!(!rdlock.contains_any::<str>(&["c", "d"]));
+        assert!(!rdlock.contains_any::<str>(&[]));
+    }
+
+    #[test]
+    fn clone() {
+        let ht = RcuHtBuilder::new()
+            .writer_shards(2)
+            .build::<u32, String>()
+            .unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i.to_string());
+            }
+        }
+
+        let copy = ht.clone();
+        assert_eq!(copy.writer_shards(), 2);
+        assert_eq!(copy.init_size(), ht.init_size());
+        {
+            let thread = copy.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            assert_eq!(wrlock.get(&10), Some(&"10".to_string()));
+            wrlock.insert_or_replace(10, "changed".to_string());
+            wrlock.remove(&11).unwrap();
+        }
+
+        let thread = ht.thread();
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.get(&10), Some(&"10".to_string()));
+        assert_eq!(rdlock.get(&11), Some(&"11".to_string()));
+        assert_eq!(rdlock.count_where(|_, _| true), 100);
+    }
+
+    #[test]
+    fn iter_from() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i);
+        }
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        let all: Vec<u32> = rdlock.iter().map(|(k, _)| *k).collect();
+        let pos = all.iter().position(|k| *k == 42).unwrap();
+        let suffix: Vec<u32> = rdlock.iter_from(&42).map(|(k, _)| *k).collect();
+        assert_eq!(suffix, all[pos..]);
+
+        assert_eq!(rdlock.iter_from(&1000).count(), 0);
+    }
+
+    #[test]
+    fn builder() {
+        let ht = RcuHtBuilder::new()
+            .init_size(16)
+            .autoresize(false)
+            .seed(42)
+            .build::<u32, u32>()
+            .unwrap();
+        assert_eq!(ht.init_size(), 16);
+        assert_eq!(ht.min_buckets(), 64);
+        assert_eq!(ht.max_buckets(), 0);
+        assert!(!ht.autoresize_enabled());
+        let thread = ht.thread();
+        thread.wrlock().unwrap().insert_or_replace(1, 1);
+        assert_eq!(thread.rdlock().get(&1), Some(&1));
+
+        let ht = RcuHtBuilder::new()
+            .hasher(std::collections::hash_map::RandomState::new())
+            .build::<String, u32>()
+            .unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace("key".to_string(), 1);
+        assert_eq!(thread.rdlock().get("key"), Some(&1));
+
+        match RcuHtBuilder::new().max_buckets(32).build::<u32, u32>() {
+            Err(RcuError::InvalidSize(_)) => (),
+            _ => panic!("min_buckets greater than max_buckets must be rejected"),
+        }
+    }
+
+    #[test]
+    fn writer_shards() {
+        let ht = RcuHtBuilder::new()
+            .writer_shards(4)
+            .build::<u32, u32>()
+            .unwrap();
+        assert_eq!(ht.writer_shards(), 4);
+
+        // concurrent writers on disjoint keys
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let ht = &ht;
+                s.spawn(move || {
+                    let thread = ht.thread();
+                    for i in (t * 1000)..((t + 1) * 1000) {
+                        let mut wrlock = thread.wrlock_for(&i).unwrap();
+                        wrlock.insert_or_replace(i, i);
+                        assert_eq!(wrlock.get(&i), Some(&i));
+                    }
+                });
+            }
+        });
+
+        let thread = ht.thread();
+        assert_eq!(thread.rdlock().count_where(|k, v| k == v), 4000);
+
+        thread.wrlock().unwrap().clear();
+        assert_eq!(thread.rdlock().count_where(|_, _| true), 0);
+
+        // a shard writer cannot write keys of other shards
+        let other = (1..4000)
+            .find(|i| ht.shard_of(ht.key_hash(i)) != ht.shard_of(ht.key_hash(&0)))
+            .unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            thread.wrlock_for(&0).unwrap().insert_or_replace(other, 0);
+        }));
+        assert!(result.is_err());
+
+        // nor merge a hashtable, even partially
+        let source = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let source_thread = source.thread();
+        let mut source_wrlock = source_thread.wrlock().unwrap();
+        (0..100).for_each(|i| source_wrlock.insert_or_replace(i, i));
+        drop(source_wrlock);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            thread
+                .wrlock_for(&0)
+                .unwrap()
+                .merge(&source_thread.rdlock());
+        }));
+        assert!(result.is_err());
+        assert_eq!(thread.rdlock().count_where(|_, _| true), 0);
+
+        assert!(RcuHtBuilder::new()
+            .writer_shards(0)
+            .build::<u32, u32>()
+            .is_err());
+    }
+
+    #[test]
+    fn lock_free_writes() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let created = std::sync::atomic::AtomicUsize::new(0);
+        let ht = RcuHtBuilder::new()
+            .lock_free_writes(true)
+            .build::<u32, DropCounter>()
+            .unwrap();
+        assert!(ht.lock_free_writes_enabled());
+
+        // many writers hammering the same keys
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let (ht, drops, created) = (&ht, &drops, &created);
+                s.spawn(move || {
+                    let thread = ht.thread();
+                    assert!(thread.wrlock().is_none());
+                    let writer = thread.concurrent_writer().unwrap();
+                    for i in 0..2000u32 {
+                        let key = i % 16;
+                        match (i + t) % 3 {
+                            0 => {
+                                let _ = writer.remove(&key);
+                            }
+                            1 => {
+                                created.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                                // on conflict, value is given back and dropped here
+                                let _ = writer.insert_if_absent(key, DropCounter(drops.clone()));
+                            }
+                            _ => {
+                                created.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                                writer.insert_or_replace(key, DropCounter(drops.clone()));
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        ht.thread().concurrent_writer().unwrap().flush_reclamation();
+        drop(ht);
+        assert_eq!(
+            drops.load(std::sync::atomic::Ordering::SeqCst),
+            created.load(std::sync::atomic::Ordering::SeqCst)
+        );
+    }
+
+    #[test]
+    fn remove_if() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace(1, 10);
+
+        assert!(!wrlock.remove_if(&1, |v| *v > 10).unwrap());
+        assert_eq!(wrlock.get(&1), Some(&10));
+        assert!(wrlock.remove_if(&1, |v| *v == 10).unwrap());
+        assert_eq!(wrlock.get(&1), None);
+        assert!(matches!(
+            wrlock.remove_if(&1, |_| true),
+            Err(RcuError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn retain_and_clear() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+
+        wrlock.retain(|key, _| key % 2 == 0);
+        wrlock.flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 50);
+        assert!(wrlock.get(&2).is_some());
+        assert!(wrlock.get(&3).is_none());
+
+        wrlock.clear();
+        wrlock.flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 100);
+        drop(wrlock);
+        assert_eq!(thread.rdlock().count_where(|_, _| true), 0);
+    }
+
+    #[test]
+    fn swap() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        assert_eq!(wrlock.swap(1, "first".to_string()), None);
+        assert_eq!(
+            wrlock.swap(1, "second".to_string()),
+            Some("first".to_string())
+        );
+        assert_eq!(wrlock.get(&1), Some(&"second".to_string()));
+    }
+
+    #[test]
+    fn get_key_value() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let key = "key".to_string();
+        thread.wrlock().unwrap().insert_or_replace(key.clone(), 1);
+
+        let rdlock = thread.rdlock();
+        let (stored_key, value) = rdlock.get_key_value("key").unwrap();
+        assert_eq!(stored_key, &key);
+        assert_ne!(stored_key.as_ptr(), key.as_ptr());
+        assert_eq!(value, &1);
+        assert_eq!(rdlock.get_key_value("other"), None);
+    }
+
+    #[test]
+    fn drop_reclamation() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+            }
+
+            // replaced values are free'd after a grace period
+            for i in 0..10 {
+                wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+            }
+
+            for i in 90..100 {
+                wrlock.remove(&i).unwrap();
+            }
+
+            wrlock.flush_reclamation();
+            assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 20);
+        }
+
+        // the 90 remaining values are dropped with the hashtable
+        drop(ht);
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 110);
+    }
+
+    #[test]
+    fn synchronous_reclamation() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHtBuilder::new()
+            .reclaim_mode(ReclaimMode::Synchronous)
+            .build::<u32, DropCounter>()
+            .unwrap();
+        assert_eq!(ht.reclaim_mode(), ReclaimMode::Synchronous);
+
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        // each removed (or replaced) value is free'd before the write returns
+        for i in 0..100 {
+            wrlock.insert_or_replace(i % 10, DropCounter(drops.clone()));
+            assert_eq!(
+                drops.load(std::sync::atomic::Ordering::SeqCst),
+                i.saturating_sub(9) as usize
+            );
+        }
+
+        for i in 0..5 {
+            wrlock.remove(&i).unwrap();
+            assert_eq!(
+                drops.load(std::sync::atomic::Ordering::SeqCst),
+                91 + i as usize
+            );
+        }
+
+        wrlock.retain(|k, _| k % 2 == 0);
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 98);
+    }
+
+    #[test]
+    fn flush_reclamation() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+
+        for i in 0..50 {
+            wrlock.remove(&i).unwrap();
+        }
+
+        wrlock.flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 50);
+    }
+
+    #[test]
+    fn insert_if_absent() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        assert!(wrlock
+            .insert_if_absent(1, DropCounter(drops.clone()))
+            .is_ok());
+
+        let conflict = wrlock.insert_if_absent(1, DropCounter(drops.clone()));
+        let value = conflict.unwrap_err();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 0);
+        drop(value);
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 1);
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        let stored = rdlock.get(&1).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&stored.0, &drops));
+        assert_eq!(rdlock.count_where(|_, _| true), 1);
+    }
+
+    #[test]
+    fn drain() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        assert!(wrlock.drain().is_empty());
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i.to_string());
+        }
+
+        let mut drained = wrlock.drain();
+        drained.sort();
+        let expected: Vec<(u32, String)> = (0..100).map(|i| (i, i.to_string())).collect();
+        assert_eq!(drained, expected);
+        drop(wrlock);
+
+        assert!(thread.rdlock().first().is_none());
+    }
+
+    #[test]
+    fn iter_with_hash() {
+        let ht = RcuHtBuilder::new().seed(42).build::<u64, u64>().unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i * 2);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        let mut count = 0;
+        for (key, value, hash) in rdlock.iter_with_hash() {
+            assert_eq!(*value, key * 2);
+            assert_eq!(hash, ht.key_hash(key));
+            count += 1;
+        }
+        assert_eq!(count, 100);
+    }
+
+    #[test]
+    fn capacity_eviction() {
+        let ht = RcuHtBuilder::new()
+            .capacity(10)
+            .build::<u32, u32>()
+            .unwrap();
+        assert_eq!(ht.capacity(), Some(10));
+        assert!(RcuHtBuilder::new().capacity(0).build::<u32, u32>().is_err());
+        assert!(matches!(
+            RcuHtBuilder::new()
+                .capacity(10)
+                .writer_shards(2)
+                .build::<u32, u32>(),
+            Err(RcuError::InvalidSize(_))
+        ));
+        assert!(matches!(
+            RcuHtBuilder::new()
+                .capacity(10)
+                .lock_free_writes(true)
+                .build::<u32, u32>(),
+            Err(RcuError::InvalidSize(
+                "capacity cannot be used with lock free writes"
+            ))
+        ));
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i);
+                assert!(thread.rdlock().count_where(|_, _| true) <= 10);
+            }
+
+            // a replaced object becomes the newest one
+            wrlock.insert_or_replace(90, 0);
+            assert!(wrlock.insert_if_absent(100, 100).is_ok());
+        }
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.count_where(|_, _| true), 10);
+        assert_eq!(rdlock.get(&90), Some(&0));
+        assert_eq!(rdlock.get(&91), None);
+        assert_eq!(rdlock.get(&100), Some(&100));
+        drop(rdlock);
+
+        // a copy keeps the insertion order: 90 is still the newest object, 92 the oldest one
+        let copy = ht.clone();
+        let copy_thread = copy.thread();
+        copy_thread.wrlock().unwrap().insert_or_replace(101, 101);
+        let rdlock = copy_thread.rdlock();
+        assert_eq!(rdlock.count_where(|_, _| true), 10);
+        assert_eq!(rdlock.get(&92), None);
+        assert_eq!(rdlock.get(&93), Some(&93));
+        assert_eq!(rdlock.get(&90), Some(&0));
+    }
+
+    #[cfg(feature = "ttl")]
+    #[test]
+    fn remove_expired() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..10 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        // replaced objects get a new insertion time
+        wrlock.insert_or_replace(0, 0);
+        wrlock.insert_or_replace(10, 10);
+
+        wrlock.remove_expired(std::time::Duration::from_millis(50));
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.count_where(|_, _| true), 2);
+        assert_eq!(rdlock.get(&0), Some(&0));
+        assert_eq!(rdlock.get(&10), Some(&10));
+    }
+
+    #[test]
+    fn get_guard() {
+        fn lookup<'thread, 'ht>(
+            thread: &'thread crate::RcuHtThread<'ht, String, String>,
+            key: &str,
+        ) -> Option<crate::RcuHtGuard<'thread, 'ht, String, String>> {
+            thread.get_guard(key)
+        }
+
+        let ht = RcuHt::<String, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace("book".to_string(), "review".to_string());
+
+        let guard = lookup(&thread, "book").unwrap();
+        assert_eq!(&*guard, "review");
+        assert_eq!(guard.len(), 6);
+        drop(guard);
+
+        assert!(lookup(&thread, "missing").is_none());
+    }
+
+    #[test]
+    fn reserve() {
+        let ht = RcuHt::<u32, u32>::new(1, 1, 4096, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.reserve(1000);
+        assert_eq!(thread.rdlock().bucket_count(), 1024);
+
+        for i in 0..1000 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        // never shrinks, and stays within maximum size
+        wrlock.reserve(0);
+        assert_eq!(thread.rdlock().bucket_count(), 1024);
+        wrlock.reserve(100_000);
+        assert_eq!(thread.rdlock().bucket_count(), 4096);
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        assert!((0..1000).all(|i| rdlock.get(&i) == Some(&i)));
+        assert!(rdlock.load_factor() <= 1.0);
+    }
+
+    #[test]
+    fn try_get() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread.wrlock().unwrap().insert_or_replace(1, 10);
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.try_get(&1).unwrap(), Some(&10));
+        assert_eq!(rdlock.try_get(&2).unwrap(), None);
+        assert_eq!(rdlock.try_get(&1).unwrap(), rdlock.get(&1));
+    }
+
+    #[cfg(feature = "qsbr")]
+    #[test]
+    fn offline_reader() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let ht = std::sync::Arc::new(ht);
+
+        let reader = {
+            let ht = ht.clone();
+            std::thread::spawn(move || {
+                let thread = ht.thread();
+                thread.offline();
+                // writer grace periods are not stalled while sleeping offline
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                thread.online();
+                assert_eq!(thread.rdlock().get(&1), Some(&2));
+            })
+        };
+
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace(1, 1);
+            assert_eq!(wrlock.swap(1, 2), Some(1));
+        }
+
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn update_all() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        // double even values only
+        wrlock.update_all(|_, v| (v % 2 == 0).then(|| v * 2));
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.count_where(|_, _| true), 100);
+        for i in 0..100 {
+            let expected = if i % 2 == 0 { i * 2 } else { i };
+            assert_eq!(rdlock.get(&i), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn with_capacity() {
+        let ht = RcuHt::<u32, u32>::with_capacity(1000).unwrap();
+        assert_eq!(ht.init_size(), 1024);
+        assert!(ht.autoresize_enabled());
+
+        let thread = ht.thread();
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..1000 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        assert!((0..1000).all(|i| rdlock.get(&i) == Some(&i)));
+    }
+
+    /// Run in each flavor build: every flavor call goes through the flavor module.
+    #[test]
+    fn flavor_calls() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        thread.wrlock().unwrap().insert_or_replace(1, 1);
+        assert_eq!(thread.rdlock().get(&1), Some(&1));
+
+        // grace periods and barriers, outside of any read-side critical section
+        crate::flavor::synchronize();
+        crate::flavor::barrier();
+
+        crate::flavor::read_lock();
+        crate::flavor::read_unlock();
+    }
+
+    #[test]
+    fn get_arc() {
+        let ht = RcuHt::<u32, std::sync::Arc<String>>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace(1, std::sync::Arc::new("shared".to_string()));
+
+        let value = thread.rdlock().get_arc(&1).unwrap();
+        assert!(thread.rdlock().get_arc(&2).is_none());
+
+        // value survives its removal from the hashtable, once the read lock is released
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.remove(&1).unwrap();
+            wrlock.flush_reclamation();
+        }
+        assert_eq!(value.as_str(), "shared");
+        assert_eq!(std::sync::Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn node_linkage() {
+        let ht = RcuHt::<u32, String>::new(1, 1, 0, true).unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..500 {
+                wrlock.insert_or_replace(i, i.to_string());
+            }
+            for i in 0..100 {
+                wrlock.remove(&i).unwrap();
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        let mut keys: Vec<u32> = rdlock
+            .iter()
+            .map(|(k, v)| {
+                assert_eq!(*v, k.to_string());
+                *k
+            })
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (100..500).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn node_pool() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHtBuilder::new()
+            .node_pool(16)
+            .build::<u32, DropCounter>()
+            .unwrap();
+        assert_eq!(ht.node_pool_capacity(), 16);
+
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+
+            for round in 0..10 {
+                for i in 0..100 {
+                    wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+                }
+                for i in 0..100 {
+                    wrlock.remove(&i).unwrap();
+                }
+
+                // free'd nodes are kept for reuse, up to the pool capacity
+                wrlock.flush_reclamation();
+                assert_eq!(
+                    drops.load(std::sync::atomic::Ordering::SeqCst),
+                    (round + 1) * 100
+                );
+                assert_eq!(ht.pool.as_ref().unwrap().len(), 16);
+            }
+
+            wrlock.insert_or_replace(0, DropCounter(drops.clone()));
+            assert_eq!(ht.pool.as_ref().unwrap().len(), 15);
+        }
+
+        drop(ht);
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 1001);
+    }
+
+    #[test]
+    fn get_with_hash() {
+        use std::hash::BuildHasher;
+
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace("key".to_string(), 1);
+
+        let hash = ht.hasher().hash_one("key");
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.get_with_hash("key", hash), rdlock.get("key"));
+        assert_eq!(rdlock.get_with_hash("key", hash), Some(&1));
+
+        let hash = ht.hasher().hash_one("missing");
+        assert_eq!(rdlock.get_with_hash("missing", hash), None);
+    }
+
+    #[test]
+    fn gc() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..1000 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+        for i in 0..1000 {
+            wrlock.remove(&i).unwrap();
+        }
+
+        wrlock.force_grace_period();
+        wrlock.gc();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 1000);
+    }
+
+    #[test]
+    fn key_eq_only_on_hash_match() {
+        static EQ_CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        struct LongKey(String);
+
+        impl std::hash::Hash for LongKey {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl PartialEq for LongKey {
+            fn eq(&self, other: &Self) -> bool {
+                EQ_CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                self.0 == other.0
+            }
+        }
+        impl Eq for LongKey {}
+
+        // few buckets: many keys share each bucket chain
+        let ht = RcuHt::<LongKey, u32>::new(4, 4, 4, false).unwrap();
+        let thread = ht.thread();
+        let key = |i: u32| LongKey(format!("{}{}", "x".repeat(256), i));
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..1000 {
+                wrlock.insert_or_replace(key(i), i);
+            }
+        }
+
+        EQ_CALLS.store(0, std::sync::atomic::Ordering::SeqCst);
+        let rdlock = thread.rdlock();
+        for i in 0..1000 {
+            assert_eq!(rdlock.get(&key(i)), Some(&i));
+        }
+        assert_eq!(rdlock.get(&key(1000)), None);
+
+        // stored hashes filter out all other keys of the chains
+        assert_eq!(EQ_CALLS.load(std::sync::atomic::Ordering::SeqCst), 1000);
+    }
+
+    /// Global allocator of tests, counting bytes allocated minus bytes free'd by each thread.
+    struct ThreadCountingAlloc;
+
+    thread_local! {
+        static THREAD_ALLOCATED: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for ThreadCountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = THREAD_ALLOCATED.try_with(|cell| cell.set(cell.get() + layout.size() as isize));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            let _ = THREAD_ALLOCATED.try_with(|cell| cell.set(cell.get() - layout.size() as isize));
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL_ALLOCATOR: ThreadCountingAlloc = ThreadCountingAlloc;
+
+    /// Bytes allocated minus bytes free'd by the current thread so far.
+    fn thread_allocated() -> isize {
+        THREAD_ALLOCATED.with(|cell| cell.get())
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i.to_string());
+            }
+        }
+
+        let ptr = ht.as_raw();
+        let allocated = thread_allocated();
+        assert_eq!(ht.into_raw(), ptr);
+
+        // everything owned is free'd, but the node pool (none here) and pending reclaims counter
+        #[cfg(not(feature = "stats"))]
+        let hit_miss = 0;
+        #[cfg(feature = "stats")]
+        let hit_miss = std::mem::size_of::<super::HitMissShard>() * super::HIT_MISS_SHARDS;
+        let freed = std::mem::size_of::<std::sync::Mutex<super::RcuHtWriterGuard<u32, String>>>()
+            + hit_miss;
+        assert_eq!(allocated - thread_allocated(), freed as isize);
+
+        let ht = unsafe { RcuHt::<u32, String>::from_raw(ptr) };
+        assert_eq!(ht.as_raw(), ptr);
+
+        let thread = ht.thread();
+        let rdlock = thread.rdlock();
+        assert!((0..100).all(|i| rdlock.get(&i) == Some(&i.to_string())));
+    }
+
+    #[test]
+    fn raw_round_trip_with_hasher_and_pool() {
+        let ht = RcuHtBuilder::new()
+            .hasher(std::collections::hash_map::RandomState::new())
+            .node_pool(16)
+            .allocator(std::alloc::System)
+            .build::<u32, String>()
+            .unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i.to_string());
+            }
+            // some free'd nodes are kept in the pool
+            assert!(wrlock.remove(&0).is_ok());
+            wrlock.flush_reclamation();
+        }
+
+        let hasher = ht.hasher().clone();
+        let ptr = ht.into_raw();
+        let ht = unsafe { RcuHt::<u32, String, _>::from_raw_with_hasher(ptr, hasher) };
+
+        let thread = ht.thread();
+        {
+            let rdlock = thread.rdlock();
+            assert!((1..100).all(|i| rdlock.get(&i) == Some(&i.to_string())));
+        }
+
+        // old nodes are free'd by the leaked pool and its allocator, new ones by the global one
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace(1, "one".to_string());
+        assert!(wrlock.remove(&2).is_ok());
+        wrlock.flush_reclamation();
+        assert_eq!(wrlock.get(&1).map(String::as_str), Some("one"));
+    }
+
+    #[test]
+    fn index() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace("key".to_string(), 1);
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock["key"], 1);
+        assert_eq!(rdlock[&"key".to_string()], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found in hashtable")]
+    fn index_missing_key() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let rdlock = thread.rdlock();
+        let _ = rdlock["missing"];
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn hit_miss() {
+        let ht = std::sync::Arc::new(RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap());
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..10 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+        assert_eq!(ht.hit_miss(), (0, 0));
+
+        // lookups from several threads: 4 * 10 hits, 4 * 30 misses
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let ht = ht.clone();
+                std::thread::spawn(move || {
+                    let thread = ht.thread();
+                    let rdlock = thread.rdlock();
+                    for i in 0..40 {
+                        rdlock.get(&i);
+                    }
+                })
+            })
+            .collect();
+        readers.into_iter().for_each(|r| r.join().unwrap());
+
+        assert_eq!(ht.hit_miss(), (40, 120));
+
+        // other lookups of a single key are counted too
+        let thread = ht.thread();
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.get_key_value(&1), Some((&1, &1)));
+        assert_eq!(rdlock.get_key_value(&10), None);
+        assert_eq!(ht.hit_miss(), (41, 121));
+    }
+
+    #[test]
+    fn get_or_try_insert_with() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        // successful insert
+        let value = wrlock.get_or_try_insert_with("a".to_string(), || Ok::<_, ()>(1));
+        assert_eq!(value, Ok(&1));
+
+        // hit: f is not called
+        let value = wrlock.get_or_try_insert_with("a".to_string(), || -> Result<u32, ()> {
+            panic!("must not be called on a hit")
+        });
+        assert_eq!(value, Ok(&1));
+
+        // failed insert: nothing is inserted
+        let value = wrlock.get_or_try_insert_with("b".to_string(), || Err("cannot load"));
+        assert_eq!(value, Err("cannot load"));
+        assert_eq!(wrlock.get("b"), None);
+        drop(wrlock);
+
+        // key is dropped on the error path
+        let key = std::sync::Arc::new(7);
+        let ht = RcuHt::<std::sync::Arc<u32>, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        let value = wrlock.get_or_try_insert_with(key.clone(), || Err(()));
+        assert_eq!(value, Err(()));
+        assert_eq!(std::sync::Arc::strong_count(&key), 1);
+    }
+
+    #[test]
+    fn iter_sorted() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in [42, 7, 99, 0, 13, 64, 1] {
+                wrlock.insert_or_replace(i, i.to_string());
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        let sorted: Vec<(u32, String)> =
+            rdlock.iter_sorted().map(|(k, v)| (*k, v.clone())).collect();
+        let expected: Vec<(u32, String)> = [0, 1, 7, 13, 42, 64, 99]
+            .iter()
+            .map(|i| (*i, i.to_string()))
+            .collect();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn is_overloaded() {
+        let ht = RcuHt::<u32, u32>::new(8, 8, 8, false).unwrap();
+        let thread = ht.thread();
+        assert!(!thread.rdlock().is_overloaded(0.5));
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+
+        // 100 objects in 8 buckets, which cannot grow
+        let rdlock = thread.rdlock();
+        assert!(rdlock.is_overloaded(2.0));
+        assert!(!rdlock.is_overloaded(100.0));
+    }
+
+    #[test]
+    fn external_read_lock() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread.wrlock().unwrap().insert_or_replace(1, 2);
+
+        // read lock held by "embedding" code
+        crate::flavor::read_lock();
+        {
+            let rdlock = unsafe { crate::RcuHtRead::from_external_lock(ht.as_raw(), &thread) };
+            assert_eq!(rdlock.get(&1), Some(&2));
+        }
+        crate::flavor::read_unlock();
+
+        // lock depth is back to 0: a grace period can elapse
+        assert_eq!(thread.wrlock().unwrap().swap(1, 3), Some(2));
+    }
+
+    #[test]
+    fn replace_all() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        wrlock.replace_all((50..150).map(|i| (i, i * 10)));
+        drop(wrlock);
+
+        let rdlock = thread.rdlock();
+        let mut pairs: Vec<(u32, u32)> = rdlock.iter().map(|(k, v)| (*k, *v)).collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, (50..150).map(|i| (i, i * 10)).collect::<Vec<_>>());
+    }
+
+    struct CountingAlloc {
+        allocs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+        deallocs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            self.allocs
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            self.deallocs
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn custom_allocator() {
+        let allocs = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let deallocs = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHtBuilder::new()
+            .allocator(CountingAlloc {
+                allocs: allocs.clone(),
+                deallocs: deallocs.clone(),
+            })
+            .build::<u32, u32>()
+            .unwrap();
+
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i);
+            }
+            for i in 0..50 {
+                wrlock.remove(&i).unwrap();
+            }
+
+            // removed nodes are free'd after a grace period, by the same allocator
+            wrlock.flush_reclamation();
+            assert_eq!(allocs.load(std::sync::atomic::Ordering::SeqCst), 100);
+            assert_eq!(deallocs.load(std::sync::atomic::Ordering::SeqCst), 50);
+        }
+
+        let copy = ht.clone();
+        drop(ht);
+        assert_eq!(allocs.load(std::sync::atomic::Ordering::SeqCst), 150);
+        assert_eq!(deallocs.load(std::sync::atomic::Ordering::SeqCst), 100);
+
+        drop(copy);
+        assert_eq!(deallocs.load(std::sync::atomic::Ordering::SeqCst), 150);
+    }
+
+    #[test]
+    fn get_or_default() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.insert_or_replace(1, 10);
+        assert_eq!(*wrlock.get_or_default(1), 10);
+        assert_eq!(*wrlock.get_or_default(2), 0);
+        assert_eq!(wrlock.get(&2), Some(&0));
+    }
+
+    #[test]
+    fn pending_reclaims() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i);
+        }
+
+        {
+            // a reader stalls grace periods (memb flavor only, read locks are no-op otherwise)
+            let _rdlock = thread.rdlock();
+            for i in 0..100 {
+                wrlock.remove(&i).unwrap();
+            }
+
+            #[cfg(feature = "memb")]
+            assert_eq!(ht.pending_reclaims(), 100);
+            assert!(ht.pending_reclaims() <= 100);
+        }
+
+        wrlock.flush_reclamation();
+        assert_eq!(ht.pending_reclaims(), 0);
+    }
+
+    #[test]
+    fn merge() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let other = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let other_thread = other.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            let mut other_wrlock = other_thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i);
+                other_wrlock.insert_or_replace(i + 50, i + 1000);
+            }
+        }
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            let other_rdlock = other_thread.rdlock();
+            wrlock.merge(&other_rdlock);
+        }
+
+        let rdlock = thread.rdlock();
+        let mut pairs: Vec<(u32, u32)> = rdlock.iter().map(|(k, v)| (*k, *v)).collect();
+        pairs.sort_unstable();
+        let expected: Vec<(u32, u32)> = (0..50)
+            .map(|i| (i, i))
+            .chain((50..150).map(|i| (i, i + 950)))
+            .collect();
+        assert_eq!(pairs, expected);
+    }
+
+    struct PanicOnDrop;
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            panic!("PanicOnDrop dropped");
+        }
+    }
+
+    #[test]
+    fn panicking_drop_aborts() {
+        const CHILD: &str = "URCU_HT_PANICKING_DROP_CHILD";
+
+        if std::env::var_os(CHILD).is_some() {
+            let ht = RcuHt::<u32, PanicOnDrop>::new(64, 64, 64, false).unwrap();
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace(1, PanicOnDrop);
+            let _ = wrlock.remove(&1);
+            // the value is dropped from urcu lib's call_rcu thread
+            wrlock.flush_reclamation();
+            unreachable!("process should have aborted");
+        }
+
+        // run this test again in a child process, which must abort
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "tests::panicking_drop_aborts",
+                "--test-threads=1",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(output.status.signal(), Some(6), "{:?}", output);
+    }
+
+    #[test]
+    fn thread_registered_once() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let registrations = || crate::flavor::REGISTRATIONS.with(|cell| cell.get());
+
+                // a long-lived handle keeps the thread registered
+                let handle = ht.thread();
+                for _ in 0..1000 {
+                    let thread = ht.thread();
+                    assert_eq!(thread.rdlock().get(&1), None);
+                }
+                let clone = handle.clone();
+                assert_eq!(clone.rdlock().get(&1), None);
+                assert_eq!(registrations(), 1);
+
+                drop(clone);
+                drop(handle);
+                let _thread = ht.thread();
+                #[cfg(not(feature = "manual_thread_lifecycle"))]
+                assert_eq!(registrations(), 2);
+                // the thread stays registered without handles
+                #[cfg(feature = "manual_thread_lifecycle")]
+                {
+                    assert_eq!(registrations(), 1);
+                    drop(_thread);
+                    crate::unregister_current_thread();
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn insert_ref() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        let value = wrlock.insert_ref(1, "one".to_string()) as *const String;
+        assert_eq!(wrlock.get(&1).map(|v| v as *const String), Some(value));
+
+        // replacing gives a reference to the new value
+        assert_eq!(wrlock.insert_ref(1, "uno".to_string()), "uno");
+        assert_eq!(wrlock.get(&1).map(String::as_str), Some("uno"));
+    }
+
+    #[cfg(feature = "fuzzing")]
+    #[test]
+    fn apply_op() {
+        use crate::Op;
+
+        let ht = RcuHt::<u8, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        let mut reference = std::collections::HashMap::new();
+
+        // deterministic pseudo random operations (xorshift)
+        let mut state: u32 = 0x1234_5678;
+        for i in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+
+            let key = (state >> 8) as u8;
+            let (op, expected) = match state % 16 {
+                0 => (Op::Clear, {
+                    reference.clear();
+                    None
+                }),
+                1 => (Op::FlushReclamation, None),
+                2..=7 => (Op::Insert(key, i), reference.insert(key, i)),
+                8..=11 => (Op::Remove(key), reference.remove(&key)),
+                _ => (Op::Get(key), reference.get(&key).cloned()),
+            };
+
+            assert_eq!(wrlock.apply_op(op), expected);
+        }
+    }
+
+    #[test]
+    fn reclaim_mode_defer_rcu() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHtBuilder::new()
+            .reclaim_mode(ReclaimMode::DeferRcu)
+            .build::<u32, DropCounter>()
+            .unwrap();
+        assert_eq!(ht.reclaim_mode(), ReclaimMode::DeferRcu);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let thread = ht.thread();
+                let mut wrlock = thread.wrlock().unwrap();
+
+                for i in 0..100 {
+                    wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+                }
+                for i in 0..100 {
+                    wrlock.remove(&i).unwrap();
+                }
+
+                wrlock.flush_reclamation();
+                assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 100);
+                assert_eq!(ht.pending_reclaims(), 0);
+
+                // objects still queued are free'd when the thread is unregistered
+                for i in 0..10 {
+                    wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+                    wrlock.remove(&i).unwrap();
+                }
+
+                // the thread stays registered without handles, until it exits
+                #[cfg(feature = "manual_thread_lifecycle")]
+                {
+                    drop(wrlock);
+                    drop(thread);
+                    crate::unregister_current_thread();
+                }
+            });
+        });
+
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 110);
+        assert_eq!(ht.pending_reclaims(), 0);
+    }
+
+    // read locks are only effective with memb flavor (qsbr readers would need quiescent states)
+    #[cfg(feature = "memb")]
+    #[test]
+    fn iter_concurrent_writes() {
+        let ht = RcuHt::<u32, u32>::new(4, 4, 0, true).unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..1000 {
+                wrlock.insert_or_replace(i, i * 2);
+            }
+        }
+
+        let stop = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let thread = ht.thread();
+                let mut round = 0;
+                while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                    let mut wrlock = thread.wrlock().unwrap();
+                    for i in 1000..2000 {
+                        wrlock.insert_or_replace(i, i * 2);
+                    }
+                    // replace stable objects, with the same value
+                    wrlock.insert_or_replace(round % 1000, (round % 1000) * 2);
+                    for i in 1000..2000 {
+                        wrlock.remove(&i).unwrap();
+                    }
+                    round += 1;
+                }
+            });
+
+            let thread = ht.thread();
+            for _ in 0..200 {
+                let rdlock = thread.rdlock();
+                let mut stable = vec![0; 1000];
+                for (key, value) in rdlock.iter() {
+                    // every yielded reference is still valid
+                    assert_eq!(*value, *key * 2);
+                    if *key < 1000 {
+                        stable[*key as usize] += 1;
+                    }
+                }
+                assert!(stable.iter().all(|&count| count == 1));
+            }
+            stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn insert_bounded() {
+        let ht = RcuHt::<u32, u32>::new(4, 4, 4, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..10 {
+            assert_eq!(wrlock.insert_bounded(i, i, 10), Ok(()));
+        }
+        assert_eq!(wrlock.insert_bounded(10, 10, 10), Err((10, 10)));
+
+        // replacing is allowed when full
+        assert_eq!(wrlock.insert_bounded(5, 50, 10), Ok(()));
+        assert_eq!(wrlock.get(&5), Some(&50));
+        assert_eq!(wrlock.get(&10), None);
+    }
+
+    #[test]
+    fn pop_any() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..100 {
+            wrlock.insert_or_replace(i, i.to_string());
+        }
+
+        let mut popped = Vec::new();
+        while let Some((key, value)) = wrlock.pop_any() {
+            assert_eq!(value, key.to_string());
+            popped.push(key);
+        }
+        popped.sort_unstable();
+        assert_eq!(popped, (0..100).collect::<Vec<_>>());
+        assert_eq!(wrlock.get(&0), None);
+    }
+
+    #[test]
+    fn get_cow() {
+        let ht = RcuHt::<u32, String>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace(1, "one".to_string());
+
+        let owned = {
+            let rdlock = thread.rdlock();
+            let value = rdlock.get_cow(&1).unwrap();
+            assert!(matches!(value, std::borrow::Cow::Borrowed(_)));
+            assert_eq!(value.len(), 3);
+            assert!(rdlock.get_cow(&2).is_none());
+
+            value.into_owned()
+        };
+        assert_eq!(owned, "one");
+    }
+
+    #[test]
+    fn thread_count_overflow() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let count = || super::URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.get());
+
+                let handles: Vec<_> = (0..10_000).map(|_| ht.thread()).collect();
+                assert_eq!(count(), 10_000);
+                drop(handles);
+                assert_eq!(count(), 0);
+
+                // the counter never wraps to 0
+                super::URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.set(u32::MAX));
+                let ret =
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(ht.thread())));
+                assert!(ret.is_err());
+                assert_eq!(count(), u32::MAX);
+                super::URCU_THREAD_REGISTERED_COUNT.with(|cell| cell.set(0));
+            });
+        });
+    }
+
+    #[test]
+    fn get_or_miss() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace(1, 10);
+            wrlock.insert_or_replace(2, 20);
+        }
+
+        let rdlock = thread.rdlock();
+        let sum = |keys: &[u32]| -> Result<u32, ()> {
+            let mut sum = 0;
+            for key in keys {
+                sum += rdlock.get_or_miss(key)?;
+            }
+            Ok(sum)
+        };
+
+        assert_eq!(sum(&[1, 2]), Ok(30));
+        assert_eq!(sum(&[1, 3, 2]), Err(()));
+    }
+
+    #[test]
+    fn thread_clone() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let registrations = || crate::flavor::REGISTRATIONS.with(|cell| cell.get());
+                let unregistrations = || crate::flavor::UNREGISTRATIONS.with(|cell| cell.get());
+
+                let thread = ht.thread();
+                let clones: Vec<_> = (0..5).map(|_| thread.clone()).collect();
+                drop(thread);
+
+                clones[0].wrlock().unwrap().insert_or_replace(1, 1);
+                for clone in clones.iter() {
+                    assert_eq!(clone.rdlock().get(&1), Some(&1));
+                }
+                assert_eq!(registrations(), 1);
+                assert_eq!(unregistrations(), 0);
+
+                drop(clones);
+                assert_eq!(registrations(), 1);
+                #[cfg(not(feature = "manual_thread_lifecycle"))]
+                assert_eq!(unregistrations(), 1);
+            });
+        });
+    }
+
+    #[test]
+    fn string_map() {
+        let map = crate::RcuStringMap::new(64, 64, 64, false).unwrap();
+        let map = std::sync::Arc::new(map);
+
+        assert_eq!(
+            map.insert(
+                "Adventures of Huckleberry Finn".to_string(),
+                "My favorite book.".to_string()
+            )
+            .unwrap(),
+            None
+        );
+        map.insert(
+            "Grimms' Fairy Tales".to_string(),
+            "Masterpiece.".to_string(),
+        )
+        .unwrap();
+
+        let child = {
+            let map = map.clone();
+            std::thread::spawn(move || map.get("Adventures of Huckleberry Finn"))
+        };
+        assert_eq!(child.join().unwrap().as_deref(), Some("My favorite book."));
+
+        assert_eq!(
+            map.insert("Grimms' Fairy Tales".to_string(), "Classic.".to_string())
+                .unwrap(),
+            Some("Masterpiece.".to_string())
+        );
+        assert!(map.contains_key("Grimms' Fairy Tales"));
+        assert!(map.remove("Grimms' Fairy Tales").is_ok());
+        assert!(!map.contains_key("Grimms' Fairy Tales"));
+        assert_eq!(map.get("Grimms' Fairy Tales"), None);
+
+        // generic API is still available
+        let thread = map.thread();
+        assert_eq!(thread.rdlock().iter().count(), 1);
+        drop(thread);
+
+        // a writer panicking while holding the lock poisons it
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let thread = map.thread();
+            let _wrlock = thread.wrlock().unwrap();
+            panic!("writer failed");
+        }));
+        assert!(matches!(
+            map.insert("key".to_string(), "value".to_string()),
+            Err(RcuError::LockPoisoned)
+        ));
+        assert!(matches!(map.remove("key"), Err(RcuError::LockPoisoned)));
+    }
+
+    #[test]
+    fn memory_estimate() {
+        let ht = RcuHt::<u64, u64>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        let buckets = 64 * std::mem::size_of::<urcu_sys::cds_lfht_node>();
+        assert_eq!(thread.rdlock().memory_estimate(), buckets);
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..1000 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+
+        // each node holds at least its key, value and urcu structs
+        let estimate = thread.rdlock().memory_estimate();
+        let node = std::mem::size_of::<super::RcuLfhtNode<u64, u64>>();
+        assert!(node >= 16 + std::mem::size_of::<urcu_sys::cds_lfht_node>());
+        assert_eq!(estimate, buckets + 1000 * node);
+    }
+
+    #[test]
+    fn remove_entry() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.insert_or_replace("one".to_string(), 1);
+
+        let (key, value) = wrlock.remove_entry("one").unwrap();
+        assert_eq!(key, "one");
+        assert_eq!(value, 1);
+        assert!(wrlock.get("one").is_none());
+        assert!(matches!(
+            wrlock.remove_entry("one"),
+            Err(RcuError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn writer_contended() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let locked = std::sync::Barrier::new(2);
+        let checked = std::sync::Barrier::new(2);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let thread = ht.thread();
+                let wrlock = thread.wrlock().unwrap();
+                locked.wait();
+                checked.wait();
+                drop(wrlock);
+            });
+
+            let thread = ht.thread();
+            locked.wait();
+            assert!(thread.writer_contended());
+            checked.wait();
+        });
+
+        assert!(!ht.thread().writer_contended());
+    }
+
+    #[test]
+    fn entry_ref() {
+        use crate::EntryRef;
+
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        assert!(matches!(wrlock.entry_ref("one"), EntryRef::Vacant(_)));
+        assert_eq!(*wrlock.entry_ref("one").or_insert(1), 1);
+        assert!(matches!(wrlock.entry_ref("one"), EntryRef::Occupied(&1)));
+
+        // the value is only computed for a vacant entry
+        assert_eq!(
+            *wrlock.entry_ref("one").or_insert_with(|| unreachable!()),
+            1
+        );
+        match wrlock.entry_ref("two") {
+            EntryRef::Vacant(entry) => assert_eq!(*entry.insert(2), 2),
+            EntryRef::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(wrlock.get("two"), Some(&2));
+    }
+
+    #[test]
+    fn update_many() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        wrlock.insert_or_replace("alice".to_string(), 100);
+        wrlock.insert_or_replace("bob".to_string(), 50);
+        wrlock.insert_or_replace("carol".to_string(), 10);
+
+        // move 30 from alice to bob, skipping a missing key
+        wrlock.update_many(&["alice", "bob", "dave"], |key, value| match key.as_str() {
+            "alice" => Some(value - 30),
+            "bob" => Some(value + 30),
+            _ => unreachable!(),
+        });
+        // None leaves the value unchanged
+        wrlock.update_many(&["carol"], |_, _| None);
+
+        assert_eq!(wrlock.get("alice"), Some(&70));
+        assert_eq!(wrlock.get("bob"), Some(&80));
+        assert_eq!(wrlock.get("carol"), Some(&10));
+        assert_eq!(wrlock.get("dave"), None);
+    }
+
+    #[test]
+    fn scoped_threads() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let ht = &ht;
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(move || {
+                    let thread = ht.thread();
+                    // objects are only inserted, never replaced: they stay valid without memb
+                    while thread.rdlock().get(&999).is_none() {
+                        std::thread::yield_now();
+                    }
+                    let rdlock = thread.rdlock();
+                    assert!((0..1000).all(|i| rdlock.get(&i) == Some(&(i * 2))));
+                });
+            }
+            s.spawn(move || {
+                let thread = ht.thread();
+                let mut wrlock = thread.wrlock().unwrap();
+                for i in 0..1000 {
+                    wrlock.insert_or_replace(i, i * 2);
+                }
+            });
+        });
+
+        assert_eq!(ht.thread().rdlock().iter().count(), 1000);
+    }
+
+    #[test]
+    fn increment() {
+        let ht = RcuHt::<&str, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 1..=100 {
+            assert_eq!(wrlock.increment("hits", 2), i * 2);
+        }
+        assert_eq!(wrlock.increment("hits", -50), 150);
+        assert_eq!(wrlock.get("hits"), Some(&150));
+
+        // out of range for u32
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.increment("misses", -1);
+        }));
+        assert!(result.is_err());
+        assert_eq!(wrlock.get("misses"), None);
+    }
+
+    #[test]
+    fn rdlock_release() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHtBuilder::new()
+            .reclaim_mode(ReclaimMode::Synchronous)
+            .build::<u32, DropCounter>()
+            .unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace(1, DropCounter(drops.clone()));
+
+        let rdlock = thread.rdlock();
+        assert!(rdlock.get(&1).is_some());
+        rdlock.release();
+
+        // a grace period from this thread would never end if the read lock was still held
+        assert!(thread.wrlock().unwrap().remove(&1).is_ok());
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(thread.rdlock().get(&1).is_none());
+    }
+
+    #[test]
+    fn count_key() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace("unique".to_string(), 0);
+            for i in 0..3 {
+                wrlock.insert_duplicate("dup".to_string(), i);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        assert_eq!(rdlock.count_key("dup"), 3);
+        assert_eq!(rdlock.count_key("unique"), 1);
+        assert_eq!(rdlock.count_key("missing"), 0);
+        let mut values: Vec<u32> = rdlock
+            .iter()
+            .filter(|(key, _)| key.as_str() == "dup")
+            .map(|(_, value)| *value)
+            .collect();
+        values.sort();
+        assert_eq!(values, [0, 1, 2]);
+    }
+
+    #[test]
+    fn multimap_writes() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let values = |ht: &RcuHt<u32, u32>, key: u32| {
+            let thread = ht.thread();
+            let rdlock = thread.rdlock();
+            let mut values: Vec<u32> = rdlock
+                .iter()
+                .filter(|(k, _)| **k == key)
+                .map(|(_, value)| *value)
+                .collect();
+            values.sort();
+            values
         };
-        */
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 10..13 {
+                wrlock.insert_duplicate(1, i);
+                wrlock.insert_duplicate(2, i);
+            }
+            wrlock.insert_or_replace(3, 0);
+
+            // each duplicate is updated once
+            wrlock.update_all(|key, value| (*key == 1).then_some(value + 100));
+            assert_eq!(values(&ht, 1), [110, 111, 112]);
+            wrlock.update_many(&[&2], |_, value| Some(value + 200));
+            assert_eq!(values(&ht, 2), [210, 211, 212]);
+
+            // any duplicate can be compared, not only the first one
+            for value in [110, 111, 112] {
+                assert!(wrlock.compare_and_swap(&1, &value, value - 100).unwrap());
+            }
+            assert!(!wrlock.compare_and_swap(&1, &110, 0).unwrap());
+            assert_eq!(values(&ht, 1), [10, 11, 12]);
+        }
+
+        // duplicates are all cloned
+        let copy = ht.clone();
+        assert_eq!(copy.thread().rdlock().count_key(&1), 3);
+        assert_eq!(values(&copy, 1), [10, 11, 12]);
+        assert_eq!(values(&copy, 2), [210, 211, 212]);
+        assert_eq!(values(&copy, 3), [0]);
+    }
+
+    #[test]
+    fn remove_one() {
+        let removals = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = {
+            let removals = removals.clone();
+            RcuHtBuilder::new()
+                .reclaim_mode(crate::ReclaimMode::Synchronous)
+                .build::<u32, u32>()
+                .unwrap()
+                .on_change(move |event| {
+                    if let crate::ChangeEvent::Remove { .. } = event {
+                        removals.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    }
+                })
+        };
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        for i in 0..3 {
+            wrlock.insert_duplicate(1, i);
+            wrlock.insert_duplicate(2, i);
+        }
+
+        assert!(wrlock.remove_one(&1).is_ok());
+        assert_eq!(thread.rdlock().count_key(&1), 2);
+
+        // remove deletes all duplicates, reported as a single removal
+        assert!(wrlock.remove(&2).is_ok());
+        assert_eq!(thread.rdlock().count_key(&2), 0);
+        assert!(matches!(wrlock.remove_one(&2), Err(RcuError::NotFound)));
+        assert!(matches!(wrlock.remove(&2), Err(RcuError::NotFound)));
+        assert_eq!(removals.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn snapshot() {
+        let ht = RcuHt::<&str, i64>::new(64, 64, 64, false).unwrap();
+        {
+            let thread = ht.thread();
+            let mut wrlock = thread.wrlock().unwrap();
+            wrlock.insert_or_replace("alice", 100);
+            wrlock.insert_or_replace("bob", 0);
+        }
+
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let thread = ht.thread();
+                for _ in 0..1000 {
+                    // move 1 from alice to bob (or back) in a single writer scope
+                    let mut wrlock = thread.wrlock().unwrap();
+                    let delta = if wrlock.get("alice") > Some(&0) {
+                        -1
+                    } else {
+                        100
+                    };
+                    wrlock.increment("alice", delta);
+                    wrlock.increment("bob", -delta);
+                }
+                done.store(true, std::sync::atomic::Ordering::SeqCst);
+            });
+
+            while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                let snapshot = ht.snapshot().unwrap();
+                assert_eq!(snapshot.len(), 2);
+                assert_eq!(snapshot["alice"] + snapshot["bob"], 100);
+            }
+        });
+
+        let snapshot = ht.snapshot().unwrap();
+        ht.thread().wrlock().unwrap().insert_or_replace("carol", 1);
+        assert_eq!(snapshot.into_inner().len(), 2);
+    }
+
+    #[test]
+    fn key_interner() {
+        let ht = RcuHtBuilder::new()
+            .reclaim_mode(ReclaimMode::Synchronous)
+            .build::<std::sync::Arc<str>, u32>()
+            .unwrap();
+        let keys = crate::KeyInterner::<str>::new();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                let key = keys.intern(["a", "b", "c"][i % 3]);
+                wrlock.insert_or_replace(key, i as u32);
+                if i % 2 == 0 {
+                    wrlock.remove(["a", "b", "c"][(i + 1) % 3]).unwrap_or(());
+                }
+            }
+        }
+
+        assert_eq!(keys.len(), 3);
+        let a = keys.intern("a");
+        assert!(std::sync::Arc::ptr_eq(&a, &keys.intern("a")));
+        assert!(thread.rdlock().get("a").is_some());
+
+        // keys still stored in hashtable are kept
+        drop(a);
+        thread.wrlock().unwrap().clear();
+        keys.purge();
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn exact_len() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        let mut keys = std::collections::HashSet::new();
+
+        for i in 0..1000u32 {
+            let key = i.wrapping_mul(2654435761) % 300;
+            wrlock.insert_or_replace(key, i);
+            keys.insert(key);
+            if i % 7 == 0 {
+                let _ = wrlock.remove(&(i % 300));
+                keys.remove(&(i % 300));
+            }
+        }
+
+        assert_eq!(wrlock.exact_len(), keys.len());
+        wrlock.clear();
+        assert_eq!(wrlock.exact_len(), 0);
+    }
+
+    #[test]
+    fn sanity_check() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        assert!(unsafe { super::urcu_sanity_check(ht.as_raw()) });
+
+        ht.thread().wrlock().unwrap().insert_or_replace(1, 1);
+        assert!(!unsafe { super::urcu_sanity_check(ht.as_raw()) });
+
+        assert_eq!(
+            RcuError::InitializationFailed.to_string(),
+            "urcu lib initialization failed"
+        );
+    }
+
+    #[test]
+    fn iter_cloned() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut expected: Vec<(String, u32)> = (0..50).map(|i| (i.to_string(), i)).collect();
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for (key, value) in expected.iter() {
+                wrlock.insert_or_replace(key.clone(), *value);
+            }
+        }
+
+        let mut pairs: Vec<(String, u32)> = thread.rdlock().iter_cloned().collect();
+        pairs.sort();
+        expected.sort();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn on_change() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Insert(String, u32),
+            Remove(String),
+        }
+        fn insert(key: &str, value: u32) -> Event {
+            Event::Insert(key.into(), value)
+        }
+        fn remove(key: &str) -> Event {
+            Event::Remove(key.into())
+        }
+
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ht = {
+            let events = events.clone();
+            RcuHt::<String, u32>::new(64, 64, 64, false)
+                .unwrap()
+                .on_change(move |event| {
+                    events.lock().unwrap().push(match event {
+                        crate::ChangeEvent::Insert { key, value } => insert(key, **value),
+                        crate::ChangeEvent::Remove { key } => remove(key),
+                    })
+                })
+        };
+        let take_events = || std::mem::take(&mut *events.lock().unwrap());
+
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace("a".into(), 10);
+        wrlock.insert_or_replace("b".into(), 20);
+        wrlock.insert_or_replace("a".into(), 11);
+        assert!(wrlock.remove("b").is_ok());
+        // failed writes are not reported
+        assert!(wrlock.remove("c").is_err());
+        assert_eq!(
+            take_events(),
+            [
+                insert("a", 10),
+                insert("b", 20),
+                insert("a", 11),
+                remove("b")
+            ]
+        );
+
+        wrlock.compute("c", |_| Some(30));
+        wrlock.compute("c", |value| value.map(|value| value + 1));
+        wrlock.compute("c", |_| None);
+        wrlock.compute("d", |_| None);
+        assert_eq!(
+            take_events(),
+            [insert("c", 30), insert("c", 31), remove("c")]
+        );
+
+        assert_eq!(wrlock.swap("a".into(), 12), Some(11));
+        assert!(wrlock.insert_if_absent("a".into(), 13).is_err());
+        assert!(wrlock.insert_if_absent("b".into(), 21).is_ok());
+        wrlock.entry_ref("c").or_insert(32);
+        assert_eq!(
+            take_events(),
+            [insert("a", 12), insert("b", 21), insert("c", 32)]
+        );
+
+        wrlock.update_all(|key, value| (key != "b").then_some(value + 1));
+        assert_eq!(wrlock.remove_entry("a").unwrap(), ("a".into(), 13));
+        let mut events = take_events();
+        events[..2].sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(events, [insert("a", 13), insert("c", 33), remove("a")]);
+
+        wrlock.clear();
+        let mut events = take_events();
+        events.sort_by_key(|event| format!("{:?}", event));
+        assert_eq!(events, [remove("b"), remove("c")]);
+        wrlock.insert_or_replace("e".into(), 50);
+        assert_eq!(wrlock.drain(), [("e".into(), 50)]);
+        assert_eq!(take_events(), [insert("e", 50), remove("e")]);
+
+        // consuming the hashtable is not reported
+        wrlock.insert_or_replace("f".into(), 60);
+        drop(wrlock);
+        drop(thread);
+        take_events();
+        assert_eq!(ht.into_iter().collect::<Vec<_>>(), [("f".into(), 60)]);
+        assert!(take_events().is_empty());
+    }
+
+    #[test]
+    fn get_range() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i, i * 10);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        let mut pairs: Vec<(u32, u32)> = rdlock
+            .get_range(10..20)
+            .into_iter()
+            .map(|(key, value)| (*key, *value))
+            .collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, (10..20).map(|i| (i, i * 10)).collect::<Vec<_>>());
+        assert_eq!(rdlock.get_range(95..).len(), 5);
+        assert!(rdlock.get_range(100..200).is_empty());
+    }
+
+    #[test]
+    fn compute() {
+        let ht = RcuHt::<String, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+
+        // increment, or remove when reaching zero
+        let add = |delta: i32| {
+            move |current: Option<&u32>| match current.copied().unwrap_or(0) as i32 + delta {
+                0 => None,
+                value => Some(value as u32),
+            }
+        };
+
+        wrlock.compute("stock", add(2));
+        assert_eq!(wrlock.get("stock"), Some(&2));
+        wrlock.compute("stock", add(3));
+        assert_eq!(wrlock.get("stock"), Some(&5));
+        wrlock.compute("stock", add(-5));
+        assert_eq!(wrlock.get("stock"), None);
+        assert_eq!(thread.rdlock().iter().count(), 0);
+
+        // nothing to remove
+        wrlock.compute("other", |_| None);
+        assert_eq!(wrlock.get("other"), None);
+    }
+
+    #[test]
+    fn split_counts() {
+        let ht = RcuHtBuilder::new()
+            .accounting(true)
+            .build::<u32, u32>()
+            .unwrap();
+        assert!(ht.accounting_enabled());
+        let thread = ht.thread();
+        assert_eq!(thread.rdlock().split_counts(), Some((0, 0)));
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..100 {
+                wrlock.insert_or_replace(i % 50, i);
+            }
+            for i in 0..10 {
+                assert!(wrlock.remove(&i).is_ok());
+            }
+        }
+        assert_eq!(thread.rdlock().split_counts(), Some((40, 40)));
+
+        // kept by clones
+        assert_eq!(ht.clone().thread().rdlock().split_counts(), Some((40, 40)));
+
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        assert_eq!(ht.thread().rdlock().split_counts(), None);
+    }
+
+    #[test]
+    fn boxed_slice_keys() {
+        let ht = RcuHt::<Box<[u8]>, usize>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let keys: Vec<Vec<u8>> = (0..100).map(|i| vec![i as u8; i % 7 + 1]).collect();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                wrlock.insert_or_replace(key.clone().into_boxed_slice(), i);
+            }
+            assert!(wrlock
+                .insert_if_absent(keys[3].clone().into_boxed_slice(), 0)
+                .is_err());
+            assert!(wrlock.remove(&keys[5][..]).is_ok());
+        }
+
+        let rdlock = thread.rdlock();
+        for (i, key) in keys.iter().enumerate() {
+            let expected = if i == 5 { None } else { Some(&i) };
+            assert_eq!(rdlock.get(key.as_slice()), expected);
+        }
+        assert_eq!(rdlock.get(&b"missing"[..]), None);
+        drop(rdlock);
+
+        let ht = RcuHt::<Box<str>, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        thread
+            .wrlock()
+            .unwrap()
+            .insert_or_replace("boxed".into(), 1);
+        assert_eq!(thread.rdlock().get("boxed"), Some(&1));
+    }
+
+    #[test]
+    fn update_all_panic() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..10 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+
+        let mut calls = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.update_all(|_, _| {
+                calls += 1;
+                if calls > 3 {
+                    panic!("update failed");
+                }
+                Some(DropCounter(drops.clone()))
+            })
+        }));
+        assert!(result.is_err());
+        drop(wrlock);
+
+        // the read lock is released: grace periods end, and replaced values are free'd
+        thread.wrlock().unwrap().flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(thread.rdlock().iter().count(), 10);
+    }
+
+    #[test]
+    fn retain_panic() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..10 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+
+        let mut calls = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.retain(|_, _| {
+                calls += 1;
+                if calls > 4 {
+                    panic!("predicate failed");
+                }
+                false
+            })
+        }));
+        assert!(result.is_err());
+        drop(wrlock);
+
+        // the read lock is released: grace periods end, and removed values are free'd
+        thread.wrlock().unwrap().flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert_eq!(thread.rdlock().iter().count(), 6);
+    }
+
+    #[test]
+    fn update_many_panic() {
+        let drops = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let ht = RcuHt::<u32, DropCounter>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        for i in 0..3 {
+            wrlock.insert_or_replace(i, DropCounter(drops.clone()));
+        }
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.update_many(&[&0, &1, &2], |key, _| match key {
+                2 => panic!("update failed"),
+                _ => Some(DropCounter(drops.clone())),
+            })
+        }));
+        assert!(result.is_err());
+        drop(wrlock);
+
+        thread.wrlock().unwrap().flush_reclamation();
+        assert_eq!(drops.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn remove_if_panic() {
+        let ht = RcuHt::<u32, u32>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace(1, 1);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.remove_if(&1, |_| panic!("predicate failed"))
+        }));
+        assert!(result.is_err());
+        drop(wrlock);
+
+        // the read lock is released: grace periods end
+        thread.wrlock().unwrap().flush_reclamation();
+        assert_eq!(thread.rdlock().get(&1), Some(&1));
+    }
+
+    #[test]
+    fn compare_and_swap_panic() {
+        #[derive(Debug)]
+        struct Uncomparable;
+
+        impl PartialEq for Uncomparable {
+            fn eq(&self, _other: &Self) -> bool {
+                panic!("cannot compare");
+            }
+        }
+
+        let ht = RcuHt::<u32, Uncomparable>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+        let mut wrlock = thread.wrlock().unwrap();
+        wrlock.insert_or_replace(1, Uncomparable);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            wrlock.compare_and_swap(&1, &Uncomparable, Uncomparable)
+        }));
+        assert!(result.is_err());
+        drop(wrlock);
+
+        // the read lock is released: grace periods end
+        thread.wrlock().unwrap().flush_reclamation();
+    }
+
+    #[test]
+    fn par_chunks() {
+        let ht = RcuHt::<u64, u64>::new(64, 64, 64, false).unwrap();
+        let thread = ht.thread();
+
+        {
+            let mut wrlock = thread.wrlock().unwrap();
+            for i in 0..1000 {
+                wrlock.insert_or_replace(i, i);
+            }
+        }
+
+        let rdlock = thread.rdlock();
+        let expected: u64 = rdlock.iter().map(|(_, v)| *v).sum();
+        assert_eq!(expected, 999 * 1000 / 2);
+
+        for n in [0, 1, 3, 8] {
+            let chunks = rdlock.par_chunks(n);
+            assert_eq!(chunks.len(), n.max(1));
+
+            let sums: Vec<(usize, u64)> = std::thread::scope(|scope| {
+                let handles: Vec<_> = chunks
+                    .into_iter()
+                    .map(|chunk| {
+                        scope.spawn(move || chunk.fold((0, 0), |(c, s), (_, v)| (c + 1, s + v)))
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+
+            assert_eq!(sums.iter().map(|(c, _)| c).sum::<usize>(), 1000);
+            assert_eq!(sums.iter().map(|(_, s)| s).sum::<u64>(), expected);
+        }
     }
 }
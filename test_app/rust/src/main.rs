@@ -5,7 +5,7 @@ extern crate urcu_ht;
 use clap::{App, Arg};
 
 use core_affinity::CoreId;
-use urcu_ht::RcuHt;
+use urcu_ht::{RcuHt, RcuHtBuilder, RcuHtThread};
 
 struct ThreadData {
     key_found: u64,
@@ -23,24 +23,35 @@ impl ThreadData {
 
 static mut GLOBAL_THREAD_DATA: Vec<ThreadData> = Vec::new();
 
-fn read_rcu(ht: Arc<RcuHt<u32, u32>>, id: usize) {
-    let thread = ht.thread();
+fn read_once(thread: &RcuHtThread<u32, u32>, thread_data: &mut ThreadData) {
+    let rdlock = thread.rdlock();
+    let val = rdlock.get(&0);
+    match val {
+        Some(_) => thread_data.key_found += 1,
+        None => thread_data.key_not_found += 1,
+    }
+    drop(rdlock);
 
+    #[cfg(feature = "qsbr")]
+    thread.quiescent_state();
+}
+
+fn read_rcu(ht: Arc<RcuHt<u32, u32>>, id: usize, churn: bool) {
     let thread_data = unsafe {
         let v = &mut GLOBAL_THREAD_DATA;
         &mut v[id]
     };
 
-    loop {
-        let rdlock = thread.rdlock();
-        let val = rdlock.get(&0);
-        match val {
-            Some(_) => thread_data.key_found += 1,
-            None => thread_data.key_not_found += 1,
+    if churn {
+        // get a new handle for each lookup, to measure thread registration overhead
+        loop {
+            read_once(&ht.thread(), thread_data);
         }
+    }
 
-        #[cfg(feature = "qsbr")]
-        thread.quiescent_state();
+    let thread = ht.thread();
+    loop {
+        read_once(&thread, thread_data);
     }
 }
 
@@ -68,6 +79,20 @@ fn main() {
                 .help("Sets a custom objects number")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("pool")
+                .short('p')
+                .long("pool")
+                .value_name("POOL")
+                .help("Sets the number of free'd nodes kept for reuse (0: no node pool)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("churn")
+                .long("churn")
+                .help("Gets a new thread handle for each read")
+                .takes_value(false),
+        )
         .arg(
             Arg::new("seconds")
                 .short('s')
@@ -97,6 +122,12 @@ fn main() {
         .unwrap_or("1")
         .parse::<u32>()
         .unwrap();
+    let pool = matches
+        .value_of("pool")
+        .unwrap_or("0")
+        .parse::<usize>()
+        .unwrap();
+    let churn = matches.is_present("churn");
     let seconds = matches
         .value_of("seconds")
         .unwrap_or("10")
@@ -118,9 +149,19 @@ fn main() {
         return;
     }
 
-    println!("{} cores used and {objects} objects changed every 1ms.", cores.len());
+    println!(
+        "{} cores used and {objects} objects changed every 1ms.",
+        cores.len()
+    );
 
-    let ht = RcuHt::new(64, 64, 64, false).expect("Cannot allocate RCU hashtable");
+    let ht: RcuHt<u32, u32> = RcuHtBuilder::new()
+        .init_size(64)
+        .min_buckets(64)
+        .max_buckets(64)
+        .autoresize(false)
+        .node_pool(pool)
+        .build()
+        .expect("Cannot allocate RCU hashtable");
     let ht = Arc::new(ht);
     let mut old_thread_data: Vec<ThreadData> = Vec::new();
 
@@ -148,7 +189,7 @@ fn main() {
             std::thread::Builder::new()
                 .stack_size(32 * 1024 * 1024)
                 .spawn(move || {
-                    read_rcu(ht, i);
+                    read_rcu(ht, i, churn);
                 })
                 .unwrap(),
         );
@@ -160,11 +201,18 @@ fn main() {
     let mut ht_write = thread.wrlock().unwrap();
     let mut now = std::time::Instant::now();
 
+    // time spent in write operations, to compare with and without node pool
+    let mut write_time = std::time::Duration::ZERO;
+    let mut writes = 0u64;
+
     let mut remaining_time = seconds;
     loop {
+        let start = std::time::Instant::now();
         for i in 0..objects {
             ht_write.insert_or_replace(i, 0);
         }
+        write_time += start.elapsed();
+        writes += objects as u64;
 
         std::thread::sleep(std::time::Duration::from_millis(1));
 
@@ -199,9 +247,12 @@ fn main() {
             }
         }
 
+        let start = std::time::Instant::now();
         for i in 0..objects {
             ht_write.remove(&i).expect("Cannot remove key");
         }
+        write_time += start.elapsed();
+        writes += objects as u64;
     }
 
     /* final computation */
@@ -224,4 +275,8 @@ fn main() {
         key_not_found / seconds,
         key_found / seconds
     );
+    println!(
+        "write: {} ns per insert or remove",
+        write_time.as_nanos() / writes as u128
+    );
 }